@@ -1,38 +1,256 @@
 pub mod key;
+pub mod mouse;
+pub mod gamepad;
+pub mod pointer;
+pub mod action;
+pub mod record;
 pub mod sdl;
 
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::borrow::Borrow;
+use std::time::Duration;
+
 use nalgebra::Vector2;
 
 use specs::prelude::*;
 
-use invader_macro::DefaultConstructor;
-use crate::input::key::{KeysRes, Key};
-use crate::misc::StateRes;
+use serde::{Serialize, Deserialize};
+
+use crate::input::key::{KeysRes, Key, Modifiers};
+use crate::input::mouse::{MouseRes, MouseButton};
+use crate::input::gamepad::{GamepadRes, GamepadButton, GamepadAxis};
+use crate::input::pointer::{PointerRes, Pointer};
+use crate::input::action::{InputMap, InputRequestQueue};
+use crate::input::record::InputRecorderRes;
+use crate::misc::{StateRes, AppState};
+use crate::physics::PhysicsRes;
 use crate::render::CameraRes;
 use crate::input::sdl::SDLInputImpl;
 
 event_queue! {
+    #[derive(Serialize, Deserialize)]
     InputEventQueue: pub enum InputEvent {
-        KeyDown(Key),
-        KeyUp(Key),
-        MouseDown(Vector2<u32>),
-        MouseUp(Vector2<u32>),
+        KeyDown(Key, Modifiers),
+        KeyRepeat(Key, Modifiers),
+        KeyUp(Key, Modifiers),
+        ScancodeDown(Key),
+        ScancodeUp(Key),
+        MouseDown(MouseButton, Vector2<i32>),
+        MouseUp(MouseButton, Vector2<i32>),
+        MouseMove(Vector2<i32>),
+        MouseWheel(Vector2<i32>),
+        // Pressure is between 0 and 1, the mouse is fully pressed while the left button is held
+        PointerDown(Pointer, Vector2<i32>, f32),
+        PointerMove(Pointer, Vector2<i32>, f32),
+        PointerUp(Pointer, Vector2<i32>),
+        GamepadConnected(usize, String),
+        GamepadDisconnected(usize),
+        GamepadDown(usize, GamepadButton),
+        GamepadUp(usize, GamepadButton),
+        GamepadAxisMotion(usize, GamepadAxis, f32),
+        TextInput(String),
+        TextEditing(String, i32, i32),
     }
 }
 
-#[derive(DefaultConstructor)]
 pub struct InputSys {
-    input: SDLInputImpl
+    input: Option<SDLInputImpl>
+}
+
+impl InputSys {
+    pub fn new(input: SDLInputImpl) -> Self {
+        Self {
+            input: Some(input)
+        }
+    }
+
+    // Only receives replayed input, so recordings can be played back in tests without a window
+    pub fn headless() -> Self {
+        Self {
+            input: None
+        }
+    }
 }
 
 impl<'a> System<'a> for InputSys {
     type SystemData = (WriteExpect<'a, StateRes>,
         Write<'a, InputEventQueue>,
         Write<'a, CameraRes>,
-        Write<'a, KeysRes>);
+        Write<'a, KeysRes>,
+        Write<'a, MouseRes>,
+        Write<'a, GamepadRes>,
+        Write<'a, PointerRes>,
+        Write<'a, InputMap>,
+        Write<'a, InputRequestQueue>,
+        Write<'a, InputRecorderRes>,
+        ReadExpect<'a, PhysicsRes>);
 
-    fn run(&mut self, (mut state, mut input_queue, mut camera, mut keys): Self::SystemData) {
+    fn run(&mut self, (mut state, mut input_queue, mut camera, mut keys, mut mouse, mut gamepads, mut pointers, mut input_map, mut requests, mut recorder, physics): Self::SystemData) {
         input_queue.clear();
-        self.input.input(&mut state, &mut camera, &mut keys, &mut input_queue);
+        keys.next_frame(physics.delta_time);
+        mouse.next_frame(physics.delta_time);
+        gamepads.next_frame(physics.delta_time);
+        pointers.next_frame();
+        mouse.wheel = Vector2::new(0, 0);
+
+        // SDL is still polled while replaying so the window keeps responding, but its events are replaced by the recorded ones
+        if let Some(input) = &mut self.input {
+            input.input(&mut state, &mut camera, &mut input_queue);
+        }
+        if let Some(events) = recorder.next_frame() {
+            input_queue.clear();
+            for event in events {
+                input_queue.push(event);
+            }
+        }
+        for event in input_queue.iter() {
+            apply_event(event, &mut keys, &mut mouse, &mut gamepads, &mut pointers);
+        }
+        if pointers.pinch_zoom {
+            camera.zoom = (camera.zoom * pointers.pinch).max(pointers.min_zoom).min(pointers.max_zoom);
+        }
+
+        recorder.record(physics.delta_time, &input_queue);
+        if state.get::<AppState>("app") == Some(&AppState::Stopping) {
+            recorder.stop_recording();
+        }
+
+        input_map.update(&keys, &mouse, &gamepads, &input_queue, &mut requests, physics.delta_time);
+    }
+}
+
+// Updates the device state from an event, this is done the same way for live and replayed input
+pub fn apply_event(event: &InputEvent, keys: &mut KeysRes, mouse: &mut MouseRes, gamepads: &mut GamepadRes, pointers: &mut PointerRes) {
+    match event {
+        InputEvent::KeyDown(key, _) => keys.press(*key),
+        InputEvent::KeyUp(key, _) => keys.release(*key),
+        InputEvent::ScancodeDown(key) => keys.press_scancode(*key),
+        InputEvent::ScancodeUp(key) => keys.release_scancode(*key),
+        InputEvent::MouseDown(button, pos) => {
+            mouse.pos = *pos;
+            mouse.press(*button);
+        },
+        InputEvent::MouseUp(button, pos) => {
+            mouse.pos = *pos;
+            mouse.release(*button);
+        },
+        InputEvent::MouseMove(pos) => mouse.pos = *pos,
+        InputEvent::MouseWheel(wheel) => mouse.wheel += wheel,
+        InputEvent::PointerDown(pointer, pos, pressure) => pointers.down(*pointer, *pos, *pressure),
+        InputEvent::PointerMove(pointer, pos, pressure) => pointers.move_to(*pointer, *pos, *pressure),
+        InputEvent::PointerUp(pointer, pos) => pointers.up(*pointer, *pos),
+        InputEvent::GamepadConnected(index, name) => gamepads.connect(*index, name),
+        InputEvent::GamepadDisconnected(index) => gamepads.disconnect(*index),
+        InputEvent::GamepadDown(index, button) => gamepads.press(*index, *button),
+        InputEvent::GamepadUp(index, button) => gamepads.release(*index, *button),
+        InputEvent::GamepadAxisMotion(index, axis, value) => gamepads.set_axis(*index, *axis, *value),
+        _ => {}
+    }
+}
+
+// Pressed buttons along with how long they have been held, and the ones that changed during the current frame
+// The hold time adds up the frame times rather than the wall clock, so replays see the same times as the recording
+#[derive(Debug)]
+pub struct ButtonStates<T: Clone + Eq + Hash> {
+    held: HashMap<T, Duration>,
+    just_pressed: HashSet<T>,
+    just_released: HashSet<T>
+}
+
+impl<T: Clone + Eq + Hash> ButtonStates<T> {
+    pub fn new() -> Self {
+        Self {
+            held: HashMap::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new()
+        }
+    }
+
+    pub fn pressed<Q: Eq + Hash + ?Sized>(&self, button: &Q) -> bool where T: Borrow<Q> {
+        self.held.contains_key(button)
+    }
+
+    pub fn just_pressed<Q: Eq + Hash + ?Sized>(&self, button: &Q) -> bool where T: Borrow<Q> {
+        self.just_pressed.contains(button)
+    }
+
+    pub fn just_released<Q: Eq + Hash + ?Sized>(&self, button: &Q) -> bool where T: Borrow<Q> {
+        self.just_released.contains(button)
+    }
+
+    pub fn held_for<Q: Eq + Hash + ?Sized>(&self, button: &Q, duration: Duration) -> bool where T: Borrow<Q> {
+        self.held.get(button).map_or(false, |held| *held >= duration)
+    }
+
+    pub fn press(&mut self, button: T) {
+        if !self.held.contains_key(&button) {
+            self.held.insert(button.clone(), Duration::from_secs(0));
+            self.just_pressed.insert(button);
+        }
+    }
+
+    pub fn release(&mut self, button: T) {
+        if self.held.remove(&button).is_some() {
+            self.just_released.insert(button);
+        }
+    }
+
+    pub fn next_frame(&mut self, delta_time: f32) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        let delta_time = Duration::from_secs_f32(delta_time.max(0.0));
+        for held in self.held.values_mut() {
+            *held += delta_time;
+        }
+    }
+}
+
+impl<T: Clone + Eq + Hash> Default for ButtonStates<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn button_hold_time_adds_up_frame_times() {
+        let mut states = ButtonStates::new();
+        states.press(1);
+        assert!(states.just_pressed(&1));
+        assert!(states.held_for(&1, Duration::from_secs(0)));
+        assert!(!states.held_for(&1, Duration::from_millis(500)));
+
+        states.next_frame(0.25);
+        assert!(!states.just_pressed(&1));
+        assert!(!states.held_for(&1, Duration::from_millis(500)));
+        states.next_frame(0.25);
+        assert!(states.held_for(&1, Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn released_button_is_no_longer_held() {
+        let mut states = ButtonStates::new();
+        states.press(1);
+        states.next_frame(1.0);
+        states.release(1);
+        assert!(states.just_released(&1));
+        assert!(!states.pressed(&1));
+        assert!(!states.held_for(&1, Duration::from_secs(0)));
+
+        states.press(1);
+        assert!(!states.held_for(&1, Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn negative_frame_time_is_ignored() {
+        let mut states = ButtonStates::new();
+        states.press(1);
+        states.next_frame(-1.0);
+        assert!(states.held_for(&1, Duration::from_secs(0)));
+        assert!(!states.held_for(&1, Duration::from_millis(1)));
     }
 }
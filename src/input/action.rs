@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::input::{ButtonStates, InputEventQueue, InputEvent};
+use crate::input::key::{Key, KeysRes};
+use crate::input::mouse::{MouseButton, MouseRes};
+use crate::input::gamepad::{GamepadButton, GamepadAxis, GamepadRes};
+
+event_queue! {
+    InputRequestQueue: pub enum InputRequest {
+        // The next key, mouse button or gamepad button that is pressed replaces the binding in the slot, Escape cancels
+        RebindAction(String, usize),
+        RebindAxis(String, usize, AxisDirection),
+        CancelRebind
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(Key),
+    // A key by its position on the keyboard, regardless of layout
+    Scancode(Key),
+    Mouse(MouseButton),
+    // A button of the controller with the index, or of any controller
+    Gamepad(Option<usize>, GamepadButton)
+}
+
+impl Binding {
+    fn pressed(binding: &Option<Binding>, devices: (&KeysRes, &MouseRes, &GamepadRes)) -> bool {
+        binding.map_or(false, |b| b.is_pressed(devices))
+    }
+
+    fn is_pressed(&self, (keys, mouse, gamepads): (&KeysRes, &MouseRes, &GamepadRes)) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Scancode(key) => keys.scancode_pressed(*key),
+            Binding::Mouse(button) => mouse.pressed(*button),
+            Binding::Gamepad(Some(index), button) => gamepads.pressed(*index, *button),
+            Binding::Gamepad(None, button) => gamepads.any_pressed(*button)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    Buttons { negative: Option<Binding>, positive: Option<Binding> },
+    Gamepad { index: Option<usize>, axis: GamepadAxis, inverted: bool }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Negative,
+    Positive
+}
+
+// Named actions and axes bound to keys and mouse buttons, evaluated by the InputSys every frame
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+    #[serde(skip)]
+    states: ButtonStates<String>,
+    #[serde(skip)]
+    values: HashMap<String, f32>,
+    #[serde(skip)]
+    capture: Option<InputRequest>
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_action(&mut self, action: &str, binding: Binding) {
+        self.actions.entry(action.to_string()).or_insert_with(Vec::new).push(binding);
+    }
+
+    pub fn remove_action(&mut self, action: &str) {
+        self.actions.remove(action);
+        self.states.release(action.to_string());
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn add_axis(&mut self, axis: &str, binding: AxisBinding) {
+        self.axes.entry(axis.to_string()).or_insert_with(Vec::new).push(binding);
+    }
+
+    pub fn remove_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+        self.values.remove(axis);
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axes.get(axis).map_or(&[], |b| b.as_slice())
+    }
+
+    pub fn pressed(&self, action: &str) -> bool {
+        self.states.pressed(action)
+    }
+
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.states.just_pressed(action)
+    }
+
+    pub fn just_released(&self, action: &str) -> bool {
+        self.states.just_released(action)
+    }
+
+    pub fn held_for(&self, action: &str, duration: Duration) -> bool {
+        self.states.held_for(action, duration)
+    }
+
+    // Between -1 and 1, the bindings of an axis are added up
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.values.get(axis).copied().unwrap_or(0.0)
+    }
+
+    pub fn rebinding(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    pub fn update(&mut self, keys: &KeysRes, mouse: &MouseRes, gamepads: &GamepadRes, input_events: &InputEventQueue, requests: &mut InputRequestQueue, delta_time: f32) {
+        let devices = (keys, mouse, gamepads);
+        for request in requests.iter() {
+            match request {
+                InputRequest::CancelRebind => self.capture = None,
+                request => self.capture = Some(request.clone())
+            }
+        }
+        requests.clear();
+
+        if self.capture.is_some() {
+            self.capture_binding(input_events);
+        }
+
+        self.states.next_frame(delta_time);
+        for (action, bindings) in self.actions.iter() {
+            match bindings.iter().any(|b| b.is_pressed(devices)) {
+                true => self.states.press(action.clone()),
+                false => self.states.release(action.clone())
+            }
+        }
+
+        for (axis, bindings) in self.axes.iter() {
+            let value: f32 = bindings.iter().map(|binding| match binding {
+                AxisBinding::Buttons { negative, positive } => {
+                    (Binding::pressed(positive, devices) as i32 - Binding::pressed(negative, devices) as i32) as f32
+                },
+                AxisBinding::Gamepad { index, axis, inverted } => {
+                    let value = match index {
+                        Some(index) => gamepads.axis(*index, *axis),
+                        None => gamepads.any_axis(*axis)
+                    };
+                    if *inverted { -value } else { value }
+                }
+            }).sum();
+            self.values.insert(axis.clone(), value.max(-1.0).min(1.0));
+        }
+    }
+
+    fn capture_binding(&mut self, input_events: &InputEventQueue) {
+        let binding = input_events.iter().find_map(|event| match event {
+            InputEvent::KeyDown(key, _) if *key != Key::Unknown => Some(Binding::Key(*key)),
+            InputEvent::MouseDown(button, _) => Some(Binding::Mouse(*button)),
+            InputEvent::GamepadDown(_, button) => Some(Binding::Gamepad(None, *button)),
+            _ => None
+        });
+
+        match (binding, self.capture.take()) {
+            (None, capture) => self.capture = capture,
+            (Some(Binding::Key(Key::Escape)), _) => (),
+            (Some(binding), Some(InputRequest::RebindAction(action, slot))) => {
+                let bindings = self.actions.entry(action).or_insert_with(Vec::new);
+                match bindings.get_mut(slot) {
+                    Some(b) => *b = binding,
+                    None => bindings.push(binding)
+                }
+            },
+            (Some(binding), Some(InputRequest::RebindAxis(axis, slot, direction))) => {
+                let bindings = self.axes.entry(axis).or_insert_with(Vec::new);
+                match (bindings.get_mut(slot), direction) {
+                    (Some(AxisBinding::Buttons { negative, .. }), AxisDirection::Negative) => *negative = Some(binding),
+                    (Some(AxisBinding::Buttons { positive, .. }), AxisDirection::Positive) => *positive = Some(binding),
+                    (Some(b), AxisDirection::Negative) => *b = AxisBinding::Buttons { negative: Some(binding), positive: None },
+                    (Some(b), AxisDirection::Positive) => *b = AxisBinding::Buttons { negative: None, positive: Some(binding) },
+                    (None, AxisDirection::Negative) => bindings.push(AxisBinding::Buttons { negative: Some(binding), positive: None }),
+                    (None, AxisDirection::Positive) => bindings.push(AxisBinding::Buttons { negative: None, positive: Some(binding) })
+                }
+            },
+            (Some(_), _) => ()
+        }
+    }
+
+    // Only the bindings are saved, as RON so that the config can be edited by hand and keys are stored by name
+    pub fn into_file(&self, file: &str) {
+        let written = ron::ser::to_string_pretty(&self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|text| std::fs::write(file, text).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("could not save input map \"{}\": {}", file, e);
+        }
+    }
+
+    pub fn from_file(file: &str) -> Option<Self> {
+        let text = std::fs::read_to_string(file).ok()?;
+        match ron::de::from_str(&text) {
+            Ok(input_map) => Some(input_map),
+            Err(e) => { eprintln!("could not parse input map \"{}\": {}", file, e); None }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::key::Modifiers;
+
+    fn update(map: &mut InputMap, keys: &KeysRes, gamepads: &GamepadRes, events: &InputEventQueue, requests: &mut InputRequestQueue) {
+        map.update(keys, &MouseRes::new(), gamepads, events, requests, 0.1);
+    }
+
+    #[test]
+    fn action_pressed_by_any_binding() {
+        let mut map = InputMap::new();
+        map.add_action("jump", Binding::Key(Key::Space));
+        map.add_action("jump", Binding::Gamepad(None, GamepadButton::A));
+        let mut keys = KeysRes::new();
+        let mut gamepads = GamepadRes::new();
+        let mut requests = InputRequestQueue::new();
+
+        update(&mut map, &keys, &gamepads, &InputEventQueue::new(), &mut requests);
+        assert!(!map.pressed("jump"));
+
+        gamepads.connect(1, "pad");
+        gamepads.press(1, GamepadButton::A);
+        update(&mut map, &keys, &gamepads, &InputEventQueue::new(), &mut requests);
+        assert!(map.pressed("jump"));
+        assert!(map.just_pressed("jump"));
+
+        gamepads.release(1, GamepadButton::A);
+        keys.press(Key::Space);
+        update(&mut map, &keys, &gamepads, &InputEventQueue::new(), &mut requests);
+        assert!(map.pressed("jump"));
+        assert!(!map.just_pressed("jump"));
+
+        keys.release(Key::Space);
+        update(&mut map, &keys, &gamepads, &InputEventQueue::new(), &mut requests);
+        assert!(map.just_released("jump"));
+    }
+
+    #[test]
+    fn axis_sums_and_clamps_bindings() {
+        let mut map = InputMap::new();
+        map.add_axis("move", AxisBinding::Buttons { negative: Some(Binding::Key(Key::A)), positive: Some(Binding::Key(Key::D)) });
+        map.add_axis("move", AxisBinding::Buttons { negative: None, positive: Some(Binding::Key(Key::Right)) });
+        let mut keys = KeysRes::new();
+        let mut requests = InputRequestQueue::new();
+
+        keys.press(Key::D);
+        keys.press(Key::Right);
+        update(&mut map, &keys, &GamepadRes::new(), &InputEventQueue::new(), &mut requests);
+        assert_eq!(map.axis("move"), 1.0);
+
+        keys.press(Key::A);
+        keys.release(Key::Right);
+        update(&mut map, &keys, &GamepadRes::new(), &InputEventQueue::new(), &mut requests);
+        assert_eq!(map.axis("move"), 0.0);
+        assert_eq!(map.axis("unknown"), 0.0);
+    }
+
+    #[test]
+    fn rebind_replaces_slot() {
+        let mut map = InputMap::new();
+        map.add_action("fire", Binding::Key(Key::F));
+        let keys = KeysRes::new();
+        let mut requests = InputRequestQueue::new();
+        requests.push(InputRequest::RebindAction("fire".to_string(), 0));
+        update(&mut map, &keys, &GamepadRes::new(), &InputEventQueue::new(), &mut requests);
+        assert!(map.rebinding());
+
+        let mut events = InputEventQueue::new();
+        events.push(InputEvent::KeyDown(Key::G, Modifiers::default()));
+        update(&mut map, &keys, &GamepadRes::new(), &events, &mut requests);
+        assert!(!map.rebinding());
+        assert_eq!(map.action_bindings("fire"), &[Binding::Key(Key::G)]);
+    }
+
+    #[test]
+    fn rebind_cancelled_by_escape() {
+        let mut map = InputMap::new();
+        map.add_action("fire", Binding::Key(Key::F));
+        let keys = KeysRes::new();
+        let mut requests = InputRequestQueue::new();
+        requests.push(InputRequest::RebindAction("fire".to_string(), 0));
+        let mut events = InputEventQueue::new();
+        events.push(InputEvent::KeyDown(Key::Escape, Modifiers::default()));
+        update(&mut map, &keys, &GamepadRes::new(), &events, &mut requests);
+        assert!(!map.rebinding());
+        assert_eq!(map.action_bindings("fire"), &[Binding::Key(Key::F)]);
+    }
+
+    #[test]
+    fn bindings_round_trip_through_file() {
+        let path = std::env::temp_dir().join(format!("invader-input-map-{}.ron", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut map = InputMap::new();
+        map.add_action("jump", Binding::Key(Key::Space));
+        map.add_axis("walk", AxisBinding::Buttons { negative: Some(Binding::Key(Key::A)), positive: Some(Binding::Key(Key::D)) });
+        map.into_file(path);
+        assert!(std::fs::read_to_string(path).unwrap().contains("Space"));
+
+        let loaded = InputMap::from_file(path).unwrap();
+        assert_eq!(loaded.actions, map.actions);
+        assert_eq!(loaded.axes, map.axes);
+
+        std::fs::write(path, "(actions: {\"jump\": [Key(NoSuchKey)]}").unwrap();
+        assert!(InputMap::from_file(path).is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::input::ButtonStates;
+
+// Controllers are identified by the index they were given when they were connected
+#[derive(Debug)]
+pub struct GamepadRes {
+    pub dead_zone: f32,
+    pads: HashMap<usize, Gamepad>
+}
+
+#[derive(Debug)]
+struct Gamepad {
+    name: String,
+    buttons: ButtonStates<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>
+}
+
+impl GamepadRes {
+    pub fn new() -> Self {
+        Self {
+            dead_zone: 0.2,
+            pads: HashMap::new()
+        }
+    }
+
+    pub fn connected(&self) -> Vec<usize> {
+        let mut connected: Vec<usize> = self.pads.keys().copied().collect();
+        connected.sort();
+        connected
+    }
+
+    pub fn is_connected(&self, index: usize) -> bool {
+        self.pads.contains_key(&index)
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.pads.get(&index).map(|pad| pad.name.as_str())
+    }
+
+    pub fn pressed(&self, index: usize, button: GamepadButton) -> bool {
+        self.pads.get(&index).map_or(false, |pad| pad.buttons.pressed(&button))
+    }
+
+    pub fn just_pressed(&self, index: usize, button: GamepadButton) -> bool {
+        self.pads.get(&index).map_or(false, |pad| pad.buttons.just_pressed(&button))
+    }
+
+    pub fn just_released(&self, index: usize, button: GamepadButton) -> bool {
+        self.pads.get(&index).map_or(false, |pad| pad.buttons.just_released(&button))
+    }
+
+    pub fn held_for(&self, index: usize, button: GamepadButton, duration: Duration) -> bool {
+        self.pads.get(&index).map_or(false, |pad| pad.buttons.held_for(&button, duration))
+    }
+
+    pub fn any_pressed(&self, button: GamepadButton) -> bool {
+        self.pads.values().any(|pad| pad.buttons.pressed(&button))
+    }
+
+    // Between -1 and 1, values within the dead zone are reported as 0 and the rest is rescaled to start from there
+    pub fn axis(&self, index: usize, axis: GamepadAxis) -> f32 {
+        let value = self.pads.get(&index).and_then(|pad| pad.axes.get(&axis)).copied().unwrap_or(0.0);
+        match value.abs() > self.dead_zone && self.dead_zone < 1.0 {
+            true => value.signum() * (value.abs() - self.dead_zone) / (1.0 - self.dead_zone),
+            false => 0.0
+        }
+    }
+
+    // The axis of any controller that is furthest from the center
+    pub fn any_axis(&self, axis: GamepadAxis) -> f32 {
+        self.pads.keys().map(|index| self.axis(*index, axis)).fold(0.0, |a, b| if b.abs() > a.abs() { b } else { a })
+    }
+
+    pub fn connect(&mut self, index: usize, name: &str) {
+        self.pads.insert(index, Gamepad {
+            name: name.to_string(),
+            buttons: ButtonStates::new(),
+            axes: HashMap::new()
+        });
+    }
+
+    pub fn disconnect(&mut self, index: usize) {
+        self.pads.remove(&index);
+    }
+
+    pub fn press(&mut self, index: usize, button: GamepadButton) {
+        if let Some(pad) = self.pads.get_mut(&index) {
+            pad.buttons.press(button);
+        }
+    }
+
+    pub fn release(&mut self, index: usize, button: GamepadButton) {
+        if let Some(pad) = self.pads.get_mut(&index) {
+            pad.buttons.release(button);
+        }
+    }
+
+    pub fn set_axis(&mut self, index: usize, axis: GamepadAxis, value: f32) {
+        if let Some(pad) = self.pads.get_mut(&index) {
+            pad.axes.insert(axis, value.clamp(-1.0, 1.0));
+        }
+    }
+
+    pub fn next_frame(&mut self, delta_time: f32) {
+        for pad in self.pads.values_mut() {
+            pad.buttons.next_frame(delta_time);
+        }
+    }
+}
+
+// Controllers get the lowest index that isn't in use, so one that is plugged back in takes the place it had before
+pub fn free_gamepad_index(used: &[usize]) -> usize {
+    (0..).find(|i| !used.contains(i)).unwrap()
+}
+
+impl Default for GamepadRes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    Back,
+    Guide,
+    Start,
+    LeftStick,
+    RightStick,
+    LeftShoulder,
+    RightShoulder,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    TriggerLeft,
+    TriggerRight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axis_dead_zone_is_rescaled() {
+        let mut gamepads = GamepadRes::new();
+        gamepads.connect(0, "pad");
+        gamepads.set_axis(0, GamepadAxis::LeftX, 0.1);
+        assert_eq!(gamepads.axis(0, GamepadAxis::LeftX), 0.0);
+        gamepads.set_axis(0, GamepadAxis::LeftX, -0.6);
+        assert!((gamepads.axis(0, GamepadAxis::LeftX) + 0.5).abs() < 1e-6);
+        gamepads.set_axis(0, GamepadAxis::LeftX, 2.0);
+        assert_eq!(gamepads.axis(0, GamepadAxis::LeftX), 1.0);
+        assert_eq!(gamepads.axis(1, GamepadAxis::LeftX), 0.0);
+    }
+
+    #[test]
+    fn any_axis_is_furthest_from_center() {
+        let mut gamepads = GamepadRes::new();
+        gamepads.connect(0, "pad");
+        gamepads.connect(1, "pad");
+        gamepads.set_axis(0, GamepadAxis::LeftY, 0.6);
+        gamepads.set_axis(1, GamepadAxis::LeftY, -1.0);
+        assert_eq!(gamepads.any_axis(GamepadAxis::LeftY), -1.0);
+        assert_eq!(gamepads.any_axis(GamepadAxis::RightY), 0.0);
+    }
+
+    #[test]
+    fn buttons_are_per_controller() {
+        let mut gamepads = GamepadRes::new();
+        gamepads.connect(0, "first");
+        gamepads.connect(1, "second");
+        gamepads.press(1, GamepadButton::A);
+        assert!(gamepads.pressed(1, GamepadButton::A));
+        assert!(gamepads.just_pressed(1, GamepadButton::A));
+        assert!(!gamepads.pressed(0, GamepadButton::A));
+        assert!(gamepads.any_pressed(GamepadButton::A));
+
+        gamepads.next_frame(0.1);
+        gamepads.release(1, GamepadButton::A);
+        assert!(gamepads.just_released(1, GamepadButton::A));
+        assert!(!gamepads.any_pressed(GamepadButton::A));
+    }
+
+    #[test]
+    fn disconnect_clears_state() {
+        let mut gamepads = GamepadRes::new();
+        gamepads.connect(0, "pad");
+        gamepads.press(0, GamepadButton::B);
+        gamepads.set_axis(0, GamepadAxis::RightX, 1.0);
+        gamepads.disconnect(0);
+        assert!(!gamepads.is_connected(0));
+        assert!(!gamepads.pressed(0, GamepadButton::B));
+
+        gamepads.connect(0, "other");
+        assert_eq!(gamepads.name(0), Some("other"));
+        assert!(!gamepads.pressed(0, GamepadButton::B));
+        assert_eq!(gamepads.axis(0, GamepadAxis::RightX), 0.0);
+    }
+
+    #[test]
+    fn unplugged_index_is_reused() {
+        assert_eq!(free_gamepad_index(&[]), 0);
+        assert_eq!(free_gamepad_index(&[0, 1, 2]), 3);
+        assert_eq!(free_gamepad_index(&[2, 0]), 1);
+    }
+}
@@ -1,31 +1,113 @@
-use std::collections::HashMap;
+use std::time::Duration;
 
+use serde::{Serialize, Deserialize};
+
+use crate::input::ButtonStates;
+
+// Keys are the logical keys of the current keyboard layout, scancodes are physical key positions named after the key in that position on a US layout
 #[derive(Default, Debug)]
-pub struct KeysRes(pub HashMap<Key, bool>);
+pub struct KeysRes {
+    keys: ButtonStates<Key>,
+    scancodes: ButtonStates<Key>
+}
 
+// Shift, Control, Opt and Gui match either the left or the right key
 impl KeysRes {
     #[allow(dead_code)]
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self {
+            keys: ButtonStates::new(),
+            scancodes: ButtonStates::new()
+        }
     }
 
     pub fn pressed(&self, key: Key) -> bool {
-        match self.0.contains_key(&key) {
-            true => self.0[&key],
-            false => false
-        }
+        pressed(&self.keys, key)
+    }
+
+    pub fn just_pressed(&self, key: Key) -> bool {
+        just_pressed(&self.keys, key)
+    }
+
+    pub fn just_released(&self, key: Key) -> bool {
+        just_released(&self.keys, key)
+    }
+
+    pub fn held_for(&self, key: Key, duration: Duration) -> bool {
+        held_for(&self.keys, key, duration)
+    }
+
+    pub fn scancode_pressed(&self, key: Key) -> bool {
+        pressed(&self.scancodes, key)
+    }
+
+    pub fn scancode_just_pressed(&self, key: Key) -> bool {
+        just_pressed(&self.scancodes, key)
+    }
+
+    pub fn scancode_just_released(&self, key: Key) -> bool {
+        just_released(&self.scancodes, key)
+    }
+
+    pub fn scancode_held_for(&self, key: Key, duration: Duration) -> bool {
+        held_for(&self.scancodes, key, duration)
     }
 
     pub fn press(&mut self, key: Key) {
-        self.0.insert(key, true);
+        self.keys.press(key);
     }
 
     pub fn release(&mut self, key: Key) {
-        self.0.insert(key, false);
+        self.keys.release(key);
+    }
+
+    pub fn press_scancode(&mut self, key: Key) {
+        self.scancodes.press(key);
+    }
+
+    pub fn release_scancode(&mut self, key: Key) {
+        self.scancodes.release(key);
+    }
+
+    pub fn next_frame(&mut self, delta_time: f32) {
+        self.keys.next_frame(delta_time);
+        self.scancodes.next_frame(delta_time);
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+fn pressed(states: &ButtonStates<Key>, key: Key) -> bool {
+    match key.sides() {
+        Some((left, right)) => states.pressed(&left) || states.pressed(&right),
+        None => states.pressed(&key)
+    }
+}
+
+fn just_pressed(states: &ButtonStates<Key>, key: Key) -> bool {
+    match key.sides() {
+        Some((left, right)) => (states.just_pressed(&left) || states.just_pressed(&right)) && !held_before(states, left) && !held_before(states, right),
+        None => states.just_pressed(&key)
+    }
+}
+
+fn just_released(states: &ButtonStates<Key>, key: Key) -> bool {
+    match key.sides() {
+        Some((left, right)) => (states.just_released(&left) || states.just_released(&right)) && !states.pressed(&left) && !states.pressed(&right),
+        None => states.just_released(&key)
+    }
+}
+
+fn held_for(states: &ButtonStates<Key>, key: Key, duration: Duration) -> bool {
+    match key.sides() {
+        Some((left, right)) => states.held_for(&left, duration) || states.held_for(&right, duration),
+        None => states.held_for(&key, duration)
+    }
+}
+
+fn held_before(states: &ButtonStates<Key>, key: Key) -> bool {
+    states.pressed(&key) && !states.just_pressed(&key)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Key {
     Unknown,
@@ -79,114 +161,260 @@ pub enum Key {
     Backspace,
     Tab,
     Return,
+    Escape,
+    Delete,
+    Home,
+    End,
     Shift,
     Control,
     Opt,
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LOpt,
+    ROpt,
     Space,
     Up,
     Down,
     Left,
-    Right
+    Right,
+    // Appended so that previously saved bindings keep their meaning
+    Gui,
+    Exclaim,
+    Quotedbl,
+    Hash,
+    Dollar,
+    Percent,
+    Ampersand,
+    LeftParen,
+    RightParen,
+    Asterisk,
+    Plus,
+    Colon,
+    Less,
+    Greater,
+    Question,
+    At,
+    Caret,
+    Underscore,
+    CapsLock,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    Insert,
+    PageUp,
+    PageDown,
+    NumLockClear,
+    KpDivide,
+    KpMultiply,
+    KpMinus,
+    KpPlus,
+    KpEnter,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+    Kp0,
+    KpPeriod,
+    Application,
+    Power,
+    KpEquals,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Execute,
+    Help,
+    Menu,
+    Select,
+    Stop,
+    Again,
+    Undo,
+    Cut,
+    Copy,
+    Paste,
+    Find,
+    Mute,
+    VolumeUp,
+    VolumeDown,
+    KpComma,
+    KpEqualsAS400,
+    AltErase,
+    SysReq,
+    Cancel,
+    Clear,
+    Prior,
+    Return2,
+    Separator,
+    Out,
+    Oper,
+    ClearAgain,
+    CrSel,
+    ExSel,
+    Kp00,
+    Kp000,
+    ThousandsSeparator,
+    DecimalSeparator,
+    CurrencyUnit,
+    CurrencySubUnit,
+    KpLeftParen,
+    KpRightParen,
+    KpLeftBrace,
+    KpRightBrace,
+    KpTab,
+    KpBackspace,
+    KpA,
+    KpB,
+    KpC,
+    KpD,
+    KpE,
+    KpF,
+    KpXor,
+    KpPower,
+    KpPercent,
+    KpLess,
+    KpGreater,
+    KpAmpersand,
+    KpDblAmpersand,
+    KpVerticalBar,
+    KpDblVerticalBar,
+    KpColon,
+    KpHash,
+    KpSpace,
+    KpAt,
+    KpExclam,
+    KpMemStore,
+    KpMemRecall,
+    KpMemClear,
+    KpMemAdd,
+    KpMemSubtract,
+    KpMemMultiply,
+    KpMemDivide,
+    KpPlusMinus,
+    KpClear,
+    KpClearEntry,
+    KpBinary,
+    KpOctal,
+    KpDecimal,
+    KpHexadecimal,
+    LGui,
+    RGui,
+    Mode,
+    AudioNext,
+    AudioPrev,
+    AudioStop,
+    AudioPlay,
+    AudioMute,
+    MediaSelect,
+    Www,
+    Mail,
+    Calculator,
+    Computer,
+    AcSearch,
+    AcHome,
+    AcBack,
+    AcForward,
+    AcStop,
+    AcRefresh,
+    AcBookmarks,
+    BrightnessDown,
+    BrightnessUp,
+    DisplaySwitch,
+    KbdIllumToggle,
+    KbdIllumDown,
+    KbdIllumUp,
+    Eject,
+    Sleep,
+    NonUsHash,
+    NonUsBackslash,
+    International1,
+    International2,
+    International3,
+    International4,
+    International5,
+    International6,
+    International7,
+    International8,
+    International9,
+    Lang1,
+    Lang2,
+    Lang3,
+    Lang4,
+    Lang5,
+    Lang6,
+    Lang7,
+    Lang8,
+    Lang9,
+    App1,
+    App2
+}
+
+// State of the modifier keys at the time of a key event
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_control: bool,
+    pub right_control: bool,
+    pub left_opt: bool,
+    pub right_opt: bool,
+    pub left_gui: bool,
+    pub right_gui: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn control(&self) -> bool {
+        self.left_control || self.right_control
+    }
+
+    pub fn opt(&self) -> bool {
+        self.left_opt || self.right_opt
+    }
+
+    pub fn gui(&self) -> bool {
+        self.left_gui || self.right_gui
+    }
 }
 
 impl Key {
-    pub fn to_char(&self, shift: bool) -> Option<char> {
+    fn sides(&self) -> Option<(Key, Key)> {
         match self {
-            Key::A if !shift => Some('a'),
-            Key::B if !shift => Some('b'),
-            Key::C if !shift => Some('c'),
-            Key::D if !shift => Some('d'),
-            Key::E if !shift => Some('e'),
-            Key::F if !shift => Some('f'),
-            Key::G if !shift => Some('g'),
-            Key::H if !shift => Some('h'),
-            Key::I if !shift => Some('i'),
-            Key::J if !shift => Some('j'),
-            Key::K if !shift => Some('k'),
-            Key::L if !shift => Some('l'),
-            Key::M if !shift => Some('m'),
-            Key::N if !shift => Some('n'),
-            Key::O if !shift => Some('o'),
-            Key::P if !shift => Some('p'),
-            Key::Q if !shift => Some('q'),
-            Key::R if !shift => Some('r'),
-            Key::S if !shift => Some('s'),
-            Key::T if !shift => Some('t'),
-            Key::U if !shift => Some('u'),
-            Key::V if !shift => Some('v'),
-            Key::W if !shift => Some('w'),
-            Key::X if !shift => Some('x'),
-            Key::Y if !shift => Some('y'),
-            Key::Z if !shift => Some('z'),
-            Key::One if !shift => Some('1'),
-            Key::Two if !shift => Some('2'),
-            Key::Three if !shift => Some('3'),
-            Key::Four if !shift => Some('4'),
-            Key::Five if !shift => Some('5'),
-            Key::Six if !shift => Some('6'),
-            Key::Seven if !shift => Some('7'),
-            Key::Eight if !shift => Some('8'),
-            Key::Nine if !shift => Some('9'),
-            Key::Zero if !shift => Some('0'),
-            Key::Minus if !shift => Some('-'),
-            Key::Equals if !shift => Some('='),
-            Key::OpenBracket if !shift => Some('['),
-            Key::CloseBracket if !shift => Some(']'),
-            Key::Backslash if !shift => Some('\\'),
-            Key::Semicolon if !shift => Some(';'),
-            Key::Apostrophe if !shift => Some('\''),
-            Key::Comma if !shift => Some(','),
-            Key::Dot if !shift => Some('.'),
-            Key::Slash if !shift => Some('/'),
-            Key::Backtick if !shift => Some('`'),
-            Key::A if shift => Some('A'),
-            Key::B if shift => Some('B'),
-            Key::C if shift => Some('C'),
-            Key::D if shift => Some('D'),
-            Key::E if shift => Some('E'),
-            Key::F if shift => Some('F'),
-            Key::G if shift => Some('G'),
-            Key::H if shift => Some('H'),
-            Key::I if shift => Some('I'),
-            Key::J if shift => Some('J'),
-            Key::K if shift => Some('K'),
-            Key::L if shift => Some('L'),
-            Key::M if shift => Some('M'),
-            Key::N if shift => Some('N'),
-            Key::O if shift => Some('O'),
-            Key::P if shift => Some('P'),
-            Key::Q if shift => Some('Q'),
-            Key::R if shift => Some('R'),
-            Key::S if shift => Some('S'),
-            Key::T if shift => Some('T'),
-            Key::U if shift => Some('U'),
-            Key::V if shift => Some('V'),
-            Key::W if shift => Some('W'),
-            Key::X if shift => Some('X'),
-            Key::Y if shift => Some('Y'),
-            Key::Z if shift => Some('Z'),
-            Key::One if shift => Some('!'),
-            Key::Two if shift => Some('@'),
-            Key::Three if shift => Some('#'),
-            Key::Four if shift => Some('$'),
-            Key::Five if shift => Some('%'),
-            Key::Six if shift => Some('^'),
-            Key::Seven if shift => Some('&'),
-            Key::Eight if shift => Some('*'),
-            Key::Nine if shift => Some('('),
-            Key::Zero if shift => Some(')'),
-            Key::Minus if shift => Some('_'),
-            Key::Equals if shift => Some('+'),
-            Key::OpenBracket if shift => Some('{'),
-            Key::CloseBracket if shift => Some('{'),
-            Key::Backslash if shift => Some('|'),
-            Key::Semicolon if shift => Some(':'),
-            Key::Apostrophe if shift => Some('"'),
-            Key::Comma if shift => Some('<'),
-            Key::Dot if shift => Some('>'),
-            Key::Slash if shift => Some('?'),
-            Key::Backtick if shift => Some('~'),
-            Key::Space => Some(' '),
+            Key::Shift => Some((Key::LShift, Key::RShift)),
+            Key::Control => Some((Key::LControl, Key::RControl)),
+            Key::Opt => Some((Key::LOpt, Key::ROpt)),
+            Key::Gui => Some((Key::LGui, Key::RGui)),
             _ => None
         }
     }
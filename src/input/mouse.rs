@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use nalgebra::Vector2;
+
+use crate::input::ButtonStates;
+
+#[derive(Debug)]
+pub struct MouseRes {
+    pub pos: Vector2<i32>,
+    pub wheel: Vector2<i32>,
+    buttons: ButtonStates<MouseButton>
+}
+
+impl MouseRes {
+    pub fn new() -> Self {
+        Self {
+            pos: Vector2::new(0, 0),
+            wheel: Vector2::new(0, 0),
+            buttons: ButtonStates::new()
+        }
+    }
+
+    pub fn pressed(&self, button: MouseButton) -> bool {
+        self.buttons.pressed(&button)
+    }
+
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.just_pressed(&button)
+    }
+
+    pub fn just_released(&self, button: MouseButton) -> bool {
+        self.buttons.just_released(&button)
+    }
+
+    pub fn held_for(&self, button: MouseButton, duration: Duration) -> bool {
+        self.buttons.held_for(&button, duration)
+    }
+
+    pub fn press(&mut self, button: MouseButton) {
+        self.buttons.press(button);
+    }
+
+    pub fn release(&mut self, button: MouseButton) {
+        self.buttons.release(button);
+    }
+
+    pub fn next_frame(&mut self, delta_time: f32) {
+        self.buttons.next_frame(delta_time);
+    }
+}
+
+impl Default for MouseRes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum MouseButton {
+    Unknown,
+    Left,
+    Middle,
+    Right,
+    X1,
+    X2
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use nalgebra::Vector2;
+
+// The mouse and every finger on a touch screen are pointers, the mouse is only pressed while the left button is held
+#[derive(Debug)]
+pub struct PointerRes {
+    // Multiplies the camera zoom by the pinch factor of each frame, keeping it between the min and max zoom
+    // Off by default, as pinches on UI elements would zoom the camera as well
+    pub pinch_zoom: bool,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    pub pinch: f32,
+    pointers: HashMap<Pointer, PointerState>
+}
+
+#[derive(Copy, Clone, Debug)]
+struct PointerState {
+    pos: Vector2<i32>,
+    pressure: f32,
+    pressed: bool
+}
+
+impl PointerRes {
+    pub fn new() -> Self {
+        Self {
+            pinch_zoom: false,
+            min_zoom: 0.25,
+            max_zoom: 4.0,
+            pinch: 1.0,
+            pointers: HashMap::new()
+        }
+    }
+
+    pub fn pointers(&self) -> Vec<Pointer> {
+        self.pointers.keys().copied().collect()
+    }
+
+    pub fn pos(&self, pointer: Pointer) -> Option<Vector2<i32>> {
+        self.pointers.get(&pointer).map(|p| p.pos)
+    }
+
+    pub fn pressure(&self, pointer: Pointer) -> f32 {
+        self.pointers.get(&pointer).map_or(0.0, |p| p.pressure)
+    }
+
+    pub fn pressed(&self, pointer: Pointer) -> bool {
+        self.pointers.get(&pointer).map_or(false, |p| p.pressed)
+    }
+
+    pub fn down(&mut self, pointer: Pointer, pos: Vector2<i32>, pressure: f32) {
+        self.pointers.insert(pointer, PointerState {
+            pos: pos,
+            pressure: pressure,
+            pressed: true
+        });
+    }
+
+    pub fn move_to(&mut self, pointer: Pointer, pos: Vector2<i32>, pressure: f32) {
+        let before = self.pinch_distance();
+        let state = self.pointers.entry(pointer).or_insert(PointerState {
+            pos: pos,
+            pressure: 0.0,
+            pressed: false
+        });
+        state.pos = pos;
+        state.pressure = pressure;
+        if let (Some(before), Some(after)) = (before, self.pinch_distance()) {
+            if before > 0.0 {
+                self.pinch *= after / before;
+            }
+        }
+    }
+
+    // Touches no longer exist once they are lifted, the mouse stays where it is
+    pub fn up(&mut self, pointer: Pointer, pos: Vector2<i32>) {
+        match pointer {
+            Pointer::Mouse => {
+                self.pointers.insert(pointer, PointerState {
+                    pos: pos,
+                    pressure: 0.0,
+                    pressed: false
+                });
+            },
+            Pointer::Touch(_) => {
+                self.pointers.remove(&pointer);
+            }
+        }
+    }
+
+    pub fn next_frame(&mut self) {
+        self.pinch = 1.0;
+    }
+
+    // A pinch is only recognised while exactly two fingers touch the screen
+    fn pinch_distance(&self) -> Option<f32> {
+        let touches: Vec<Vector2<f32>> = self.pointers.iter()
+            .filter(|(pointer, state)| matches!(pointer, Pointer::Touch(_)) && state.pressed)
+            .map(|(_, state)| Vector2::new(state.pos.x as f32, state.pos.y as f32))
+            .collect();
+        match touches.as_slice() {
+            [a, b] => Some((a - b).norm()),
+            _ => None
+        }
+    }
+}
+
+impl Default for PointerRes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Pointer {
+    Mouse,
+    // The finger id reported by the touch device
+    Touch(i64)
+}
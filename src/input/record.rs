@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+
+use serde::{Serialize, Deserialize};
+
+use crate::input::{InputEventQueue, InputEvent};
+
+// Records the input of every frame to a file, or plays a recording back in place of the real input
+#[derive(Default, Debug)]
+pub struct InputRecorderRes {
+    recording: Option<BufWriter<File>>,
+    replay: Option<(InputRecording, usize)>
+}
+
+impl InputRecorderRes {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self {
+            recording: None,
+            replay: None
+        }
+    }
+
+    // Frames are appended to the file as they are recorded, so a crash only loses the frame in progress
+    pub fn start_recording(&mut self, file: &str) {
+        self.stop_recording();
+        match File::create(file) {
+            Ok(f) => self.recording = Some(BufWriter::new(f)),
+            Err(e) => eprintln!("could not create input recording \"{}\": {}", file, e)
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(mut recording) = self.recording.take() {
+            if let Err(e) = recording.flush() {
+                eprintln!("could not write input recording: {}", e);
+            }
+        }
+    }
+
+    pub fn start_replay(&mut self, file: &str) {
+        match InputRecording::from_file(file) {
+            Some(recording) => self.replay(recording),
+            None => eprintln!("could not load input recording \"{}\"", file)
+        }
+    }
+
+    pub fn replay(&mut self, recording: InputRecording) {
+        self.replay = Some((recording, 0));
+    }
+
+    // Live input is used again once the replay is stopped or has run out of frames
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    pub fn recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // The frame time of the frame that will be replayed next
+    pub fn delta_time(&self) -> Option<f32> {
+        self.replay.as_ref().and_then(|(recording, frame)| recording.frames.get(*frame)).map(|frame| frame.delta_time)
+    }
+
+    pub fn next_frame(&mut self) -> Option<Vec<InputEvent>> {
+        let frame = match &mut self.replay {
+            Some((recording, frame)) => {
+                *frame += 1;
+                recording.frames.get(*frame - 1).map(|frame| frame.events.clone())
+            },
+            None => None
+        };
+        if frame.is_none() {
+            self.replay = None;
+        }
+        frame
+    }
+
+    pub fn record(&mut self, delta_time: f32, input_events: &InputEventQueue) {
+        if let Some(recording) = &mut self.recording {
+            let frame = InputFrame {
+                delta_time: delta_time,
+                events: input_events.iter().cloned().collect()
+            };
+            let written = bincode::serialize_into(&mut *recording, &frame)
+                .map_err(|e| e.to_string())
+                .and_then(|_| recording.flush().map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                eprintln!("could not write input recording: {}", e);
+                self.recording = None;
+            }
+        }
+    }
+}
+
+impl Drop for InputRecorderRes {
+    fn drop(&mut self) {
+        self.stop_recording();
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub frames: Vec<InputFrame>
+}
+
+impl InputRecording {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new()
+        }
+    }
+
+    // The file is a plain sequence of frames, the same as InputRecorderRes writes
+    pub fn into_file(&self, file: &str) {
+        let written = File::create(file).map_err(|e| e.to_string()).and_then(|f| {
+            let mut f = BufWriter::new(f);
+            for frame in &self.frames {
+                bincode::serialize_into(&mut f, frame).map_err(|e| e.to_string())?;
+            }
+            f.flush().map_err(|e| e.to_string())
+        });
+        if let Err(e) = written {
+            eprintln!("could not write input recording \"{}\": {}", file, e);
+        }
+    }
+
+    // A frame cut off at the end of the file is ignored, as it was still being written when the app stopped
+    pub fn from_file(file: &str) -> Option<Self> {
+        let mut reader = BufReader::new(File::open(file).ok()?);
+        let mut frames = Vec::new();
+        loop {
+            match bincode::deserialize_from(&mut reader) {
+                Ok(frame) => frames.push(frame),
+                Err(e) => match *e {
+                    bincode::ErrorKind::Io(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                    _ => {
+                        eprintln!("could not parse input recording \"{}\": {}", file, e);
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(Self {
+            frames: frames
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputFrame {
+    pub delta_time: f32,
+    pub events: Vec<InputEvent>
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use specs::prelude::*;
+
+    use super::*;
+    use crate::input::InputSys;
+    use crate::input::key::{KeysRes, Key, Modifiers};
+    use crate::misc::StateRes;
+    use crate::physics::PhysicsRes;
+
+    fn world(recorder: InputRecorderRes) -> World {
+        let mut world = World::new();
+        world.insert(StateRes::new());
+        world.insert(PhysicsRes::new());
+        world.insert(recorder);
+        world
+    }
+
+    // Runs the input system until the replay ends, noting whether A is pressed and has been held for a second after each frame
+    fn play(world: &mut World) -> Vec<(bool, bool)> {
+        let mut input = InputSys::headless();
+        System::setup(&mut input, world);
+        let mut states = Vec::new();
+        loop {
+            let delta_time = world.read_resource::<InputRecorderRes>().delta_time();
+            let delta_time = match delta_time {
+                Some(delta_time) => delta_time,
+                None => break
+            };
+            world.write_resource::<PhysicsRes>().delta_time = delta_time;
+            input.run_now(world);
+            let keys = world.read_resource::<KeysRes>();
+            states.push((keys.pressed(Key::A), keys.held_for(Key::A, Duration::from_secs(1))));
+        }
+        states
+    }
+
+    fn frame(events: Vec<InputEvent>) -> InputFrame {
+        InputFrame {
+            delta_time: 0.4,
+            events: events
+        }
+    }
+
+    #[test]
+    fn recorded_session_replays_the_same() {
+        let file = std::env::temp_dir().join(format!("invader-input-{}.mir", std::process::id()));
+        let file = file.to_str().unwrap();
+
+        let mut script = InputRecording::new();
+        script.frames.push(frame(vec![InputEvent::KeyDown(Key::A, Modifiers::default())]));
+        for _ in 0..4 {
+            script.frames.push(frame(Vec::new()));
+        }
+        script.frames.push(frame(vec![InputEvent::KeyUp(Key::A, Modifiers::default())]));
+        let expected = vec![(true, false), (true, false), (true, false), (true, true), (true, true), (false, false)];
+
+        let mut recorder = InputRecorderRes::new();
+        recorder.replay(script);
+        recorder.start_recording(file);
+        let mut w = world(recorder);
+        assert_eq!(play(&mut w), expected);
+        drop(w);
+
+        let mut recorder = InputRecorderRes::new();
+        recorder.start_replay(file);
+        assert!(recorder.replaying());
+        assert_eq!(play(&mut world(recorder)), expected);
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn recording_is_written_as_it_goes() {
+        let file = std::env::temp_dir().join(format!("invader-input-partial-{}.mir", std::process::id()));
+        let file = file.to_str().unwrap();
+
+        let mut recorder = InputRecorderRes::new();
+        recorder.start_recording(file);
+        let mut events = InputEventQueue::default();
+        events.push(InputEvent::TextInput("a".to_string()));
+        recorder.record(0.1, &events);
+        recorder.record(0.2, &InputEventQueue::default());
+
+        let recording = InputRecording::from_file(file).unwrap();
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.frames[1].delta_time, 0.2);
+        drop(recorder);
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn truncated_recording_keeps_complete_frames() {
+        let file = std::env::temp_dir().join(format!("invader-input-truncated-{}.mir", std::process::id()));
+        let file = file.to_str().unwrap();
+
+        let mut recording = InputRecording::new();
+        recording.frames.push(frame(vec![InputEvent::TextInput("a".to_string())]));
+        recording.frames.push(frame(vec![InputEvent::TextInput("b".to_string())]));
+        recording.into_file(file);
+        let len = std::fs::metadata(file).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(file).unwrap().set_len(len - 2).unwrap();
+
+        assert_eq!(InputRecording::from_file(file).unwrap().frames.len(), 1);
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -1,20 +1,30 @@
 use sdl2::*;
 use sdl2::event::*;
 use sdl2::keyboard::*;
+use sdl2::mouse::{MouseWheelDirection, MouseButton as SDLMouseButton};
+use sdl2::controller::{GameController, Button as SDLButton, Axis as SDLAxis};
+
+use std::collections::HashMap;
 
 use nalgebra::Vector2;
 
 use crate::input::{InputEventQueue, InputEvent};
-use crate::input::key::{KeysRes, Key};
+use crate::input::key::{Key, Modifiers};
+use crate::input::mouse::MouseButton;
+use crate::input::gamepad::{GamepadButton, GamepadAxis, free_gamepad_index};
+use crate::input::pointer::Pointer;
 use crate::misc::{StateRes, AppState};
 use crate::render::CameraRes;
 
+// SDL_TOUCH_MOUSEID, the mouse id of mouse events that were generated from touches
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
 pub struct SDLInputImpl {
     context: SDLContext
 }
 
 impl SDLInputImpl {
-    pub fn input(&mut self, state: &mut StateRes, camera: &mut CameraRes, keys: &mut KeysRes, input_queue: &mut InputEventQueue) {
+    pub fn input(&mut self, state: &mut StateRes, camera: &mut CameraRes, input_queue: &mut InputEventQueue) {
         for event in self.context.events.poll_iter() {
             match event {
                 Event::Quit {..} => state.insert("app", AppState::Stopping),
@@ -24,16 +34,76 @@ impl SDLInputImpl {
                         _ => {}
                     }
                 },
-                Event::KeyDown { keycode: Some(k), .. } => {
-                    keys.press(sdl_to_key(k));
-                    input_queue.push(InputEvent::KeyDown(sdl_to_key(k)));
+                Event::KeyDown { keycode, scancode, keymod, repeat, .. } => {
+                    if let (Some(s), false) = (scancode, repeat) {
+                        input_queue.push(InputEvent::ScancodeDown(sdl_to_scancode(s)));
+                    }
+                    // Keys that only exist on some layouts have no keycode, text from them still arrives through TextInput
+                    let key = keycode.map_or(Key::Unknown, sdl_to_key);
+                    match repeat {
+                        true => input_queue.push(InputEvent::KeyRepeat(key, sdl_to_modifiers(keymod))),
+                        false => input_queue.push(InputEvent::KeyDown(key, sdl_to_modifiers(keymod)))
+                    }
+                },
+                Event::KeyUp { keycode, scancode, keymod, .. } => {
+                    if let Some(s) = scancode {
+                        input_queue.push(InputEvent::ScancodeUp(sdl_to_scancode(s)));
+                    }
+                    input_queue.push(InputEvent::KeyUp(keycode.map_or(Key::Unknown, sdl_to_key), sdl_to_modifiers(keymod)));
+                },
+                // Mouse events that SDL generates from touches are already reported as touch pointers
+                Event::MouseButtonDown { which, mouse_btn, x, y, .. } => {
+                    input_queue.push(InputEvent::MouseDown(sdl_to_mouse_button(mouse_btn), Vector2::new(x, y)));
+                    if mouse_btn == SDLMouseButton::Left && which != TOUCH_MOUSE_ID {
+                        input_queue.push(InputEvent::PointerDown(Pointer::Mouse, Vector2::new(x, y), 1.0));
+                    }
+                },
+                Event::MouseButtonUp { which, mouse_btn, x, y, .. } => {
+                    input_queue.push(InputEvent::MouseUp(sdl_to_mouse_button(mouse_btn), Vector2::new(x, y)));
+                    if mouse_btn == SDLMouseButton::Left && which != TOUCH_MOUSE_ID {
+                        input_queue.push(InputEvent::PointerUp(Pointer::Mouse, Vector2::new(x, y)));
+                    }
+                },
+                Event::MouseMotion { which, mousestate, x, y, .. } => {
+                    input_queue.push(InputEvent::MouseMove(Vector2::new(x, y)));
+                    if which != TOUCH_MOUSE_ID {
+                        input_queue.push(InputEvent::PointerMove(Pointer::Mouse, Vector2::new(x, y), if mousestate.left() { 1.0 } else { 0.0 }));
+                    }
                 },
-                Event::KeyUp { keycode: Some(k), .. } => {
-                    keys.release(sdl_to_key(k));
-                    input_queue.push(InputEvent::KeyUp(sdl_to_key(k)));
+                Event::FingerDown { finger_id, x, y, pressure, .. } => input_queue.push(InputEvent::PointerDown(Pointer::Touch(finger_id), touch_to_screen(x, y, camera), pressure)),
+                Event::FingerMotion { finger_id, x, y, pressure, .. } => input_queue.push(InputEvent::PointerMove(Pointer::Touch(finger_id), touch_to_screen(x, y, camera), pressure)),
+                Event::FingerUp { finger_id, x, y, .. } => input_queue.push(InputEvent::PointerUp(Pointer::Touch(finger_id), touch_to_screen(x, y, camera))),
+                Event::MouseWheel { x, y, direction, .. } => {
+                    let wheel = match direction {
+                        MouseWheelDirection::Flipped => Vector2::new(-x, -y),
+                        _ => Vector2::new(x, y)
+                    };
+                    input_queue.push(InputEvent::MouseWheel(wheel));
                 },
-                Event::MouseButtonDown { x, y, .. } => input_queue.push(InputEvent::MouseDown(Vector2::new(x as u32, y as u32))),
-                Event::MouseButtonUp { x, y, .. } => input_queue.push(InputEvent::MouseUp(Vector2::new(x as u32, y as u32))),
+                Event::ControllerDeviceAdded { which, .. } => match self.context.controller.open(which) {
+                    Ok(controller) => {
+                        let used: Vec<usize> = self.context.controllers.values().map(|(index, _)| *index).collect();
+                        let index = free_gamepad_index(&used);
+                        input_queue.push(InputEvent::GamepadConnected(index, controller.name()));
+                        self.context.controllers.insert(controller.instance_id(), (index, controller));
+                    },
+                    Err(e) => eprintln!("could not open game controller {}: {}", which, e)
+                },
+                Event::ControllerDeviceRemoved { which, .. } => if let Some((index, _)) = self.context.controllers.remove(&which) {
+                    input_queue.push(InputEvent::GamepadDisconnected(index));
+                },
+                Event::ControllerButtonDown { which, button, .. } => if let Some((index, _)) = self.context.controllers.get(&which) {
+                    input_queue.push(InputEvent::GamepadDown(*index, sdl_to_gamepad_button(button)));
+                },
+                Event::ControllerButtonUp { which, button, .. } => if let Some((index, _)) = self.context.controllers.get(&which) {
+                    input_queue.push(InputEvent::GamepadUp(*index, sdl_to_gamepad_button(button)));
+                },
+                Event::ControllerAxisMotion { which, axis, value, .. } => if let Some((index, _)) = self.context.controllers.get(&which) {
+                    let value = (value as f32 / i16::MAX as f32).max(-1.0);
+                    input_queue.push(InputEvent::GamepadAxisMotion(*index, sdl_to_gamepad_axis(axis), value));
+                },
+                Event::TextInput { text, .. } => input_queue.push(InputEvent::TextInput(text)),
+                Event::TextEditing { text, start, length, .. } => input_queue.push(InputEvent::TextEditing(text, start, length)),
                 _ => {}
             }
         }
@@ -41,9 +111,12 @@ impl SDLInputImpl {
 
     pub fn init(sdl_context: &Sdl) -> Self {
         let events = sdl_context.event_pump().unwrap();
+        let controller = sdl_context.game_controller().unwrap();
 
         let context = SDLContext {
-            events: events
+            events: events,
+            controller: controller,
+            controllers: HashMap::new()
         };
 
         Self {
@@ -54,6 +127,49 @@ impl SDLInputImpl {
 
 fn sdl_to_key(k: Keycode) -> Key {
     match k {
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Tab => Key::Tab,
+        Keycode::Return => Key::Return,
+        Keycode::Escape => Key::Escape,
+        Keycode::Space => Key::Space,
+        Keycode::Exclaim => Key::Exclaim,
+        Keycode::Quotedbl => Key::Quotedbl,
+        Keycode::Hash => Key::Hash,
+        Keycode::Dollar => Key::Dollar,
+        Keycode::Percent => Key::Percent,
+        Keycode::Ampersand => Key::Ampersand,
+        Keycode::Quote => Key::Apostrophe,
+        Keycode::LeftParen => Key::LeftParen,
+        Keycode::RightParen => Key::RightParen,
+        Keycode::Asterisk => Key::Asterisk,
+        Keycode::Plus => Key::Plus,
+        Keycode::Comma => Key::Comma,
+        Keycode::Minus => Key::Minus,
+        Keycode::Period => Key::Dot,
+        Keycode::Slash => Key::Slash,
+        Keycode::Num0 => Key::Zero,
+        Keycode::Num1 => Key::One,
+        Keycode::Num2 => Key::Two,
+        Keycode::Num3 => Key::Three,
+        Keycode::Num4 => Key::Four,
+        Keycode::Num5 => Key::Five,
+        Keycode::Num6 => Key::Six,
+        Keycode::Num7 => Key::Seven,
+        Keycode::Num8 => Key::Eight,
+        Keycode::Num9 => Key::Nine,
+        Keycode::Colon => Key::Colon,
+        Keycode::Semicolon => Key::Semicolon,
+        Keycode::Less => Key::Less,
+        Keycode::Equals => Key::Equals,
+        Keycode::Greater => Key::Greater,
+        Keycode::Question => Key::Question,
+        Keycode::At => Key::At,
+        Keycode::LeftBracket => Key::OpenBracket,
+        Keycode::Backslash => Key::Backslash,
+        Keycode::RightBracket => Key::CloseBracket,
+        Keycode::Caret => Key::Caret,
+        Keycode::Underscore => Key::Underscore,
+        Keycode::Backquote => Key::Backtick,
         Keycode::A => Key::A,
         Keycode::B => Key::B,
         Keycode::C => Key::C,
@@ -80,45 +196,486 @@ fn sdl_to_key(k: Keycode) -> Key {
         Keycode::X => Key::X,
         Keycode::Y => Key::Y,
         Keycode::Z => Key::Z,
-        Keycode::Num1 => Key::One,
-        Keycode::Num2 => Key::Two,
-        Keycode::Num3 => Key::Three,
-        Keycode::Num4 => Key::Four,
-        Keycode::Num5 => Key::Five,
-        Keycode::Num6 => Key::Six,
-        Keycode::Num7 => Key::Seven,
-        Keycode::Num8 => Key::Eight,
-        Keycode::Num9 => Key::Nine,
-        Keycode::Num0 => Key::Zero,
-        Keycode::Minus => Key::Minus,
-        Keycode::Equals => Key::Equals,
-        Keycode::LeftBracket => Key::OpenBracket,
-        Keycode::RightBracket => Key::CloseBracket,
-        Keycode::Backslash => Key::Backslash,
-        Keycode::Semicolon => Key::Semicolon,
-        Keycode::Quote => Key::Apostrophe,
-        Keycode::Comma => Key::Comma,
-        Keycode::Period => Key::Dot,
-        Keycode::Slash => Key::Slash,
-        Keycode::Backquote => Key::Backtick,
-        Keycode::Backspace => Key::Backspace,
-        Keycode::Tab => Key::Tab,
-        Keycode::Return => Key::Return,
-        Keycode::LShift => Key::Shift,
-        Keycode::RShift => Key::Shift,
-        Keycode::LCtrl => Key::Control,
-        Keycode::RCtrl => Key::Control,
-        Keycode::LAlt => Key::Opt,
-        Keycode::RAlt => Key::Opt,
-        Keycode::Space => Key::Space,
-        Keycode::Up => Key::Up,
-        Keycode::Down => Key::Down,
-        Keycode::Left => Key::Left,
+        Keycode::Delete => Key::Delete,
+        Keycode::CapsLock => Key::CapsLock,
+        Keycode::F1 => Key::F1,
+        Keycode::F2 => Key::F2,
+        Keycode::F3 => Key::F3,
+        Keycode::F4 => Key::F4,
+        Keycode::F5 => Key::F5,
+        Keycode::F6 => Key::F6,
+        Keycode::F7 => Key::F7,
+        Keycode::F8 => Key::F8,
+        Keycode::F9 => Key::F9,
+        Keycode::F10 => Key::F10,
+        Keycode::F11 => Key::F11,
+        Keycode::F12 => Key::F12,
+        Keycode::PrintScreen => Key::PrintScreen,
+        Keycode::ScrollLock => Key::ScrollLock,
+        Keycode::Pause => Key::Pause,
+        Keycode::Insert => Key::Insert,
+        Keycode::Home => Key::Home,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::End => Key::End,
+        Keycode::PageDown => Key::PageDown,
         Keycode::Right => Key::Right,
+        Keycode::Left => Key::Left,
+        Keycode::Down => Key::Down,
+        Keycode::Up => Key::Up,
+        Keycode::NumLockClear => Key::NumLockClear,
+        Keycode::KpDivide => Key::KpDivide,
+        Keycode::KpMultiply => Key::KpMultiply,
+        Keycode::KpMinus => Key::KpMinus,
+        Keycode::KpPlus => Key::KpPlus,
+        Keycode::KpEnter => Key::KpEnter,
+        Keycode::Kp1 => Key::Kp1,
+        Keycode::Kp2 => Key::Kp2,
+        Keycode::Kp3 => Key::Kp3,
+        Keycode::Kp4 => Key::Kp4,
+        Keycode::Kp5 => Key::Kp5,
+        Keycode::Kp6 => Key::Kp6,
+        Keycode::Kp7 => Key::Kp7,
+        Keycode::Kp8 => Key::Kp8,
+        Keycode::Kp9 => Key::Kp9,
+        Keycode::Kp0 => Key::Kp0,
+        Keycode::KpPeriod => Key::KpPeriod,
+        Keycode::Application => Key::Application,
+        Keycode::Power => Key::Power,
+        Keycode::KpEquals => Key::KpEquals,
+        Keycode::F13 => Key::F13,
+        Keycode::F14 => Key::F14,
+        Keycode::F15 => Key::F15,
+        Keycode::F16 => Key::F16,
+        Keycode::F17 => Key::F17,
+        Keycode::F18 => Key::F18,
+        Keycode::F19 => Key::F19,
+        Keycode::F20 => Key::F20,
+        Keycode::F21 => Key::F21,
+        Keycode::F22 => Key::F22,
+        Keycode::F23 => Key::F23,
+        Keycode::F24 => Key::F24,
+        Keycode::Execute => Key::Execute,
+        Keycode::Help => Key::Help,
+        Keycode::Menu => Key::Menu,
+        Keycode::Select => Key::Select,
+        Keycode::Stop => Key::Stop,
+        Keycode::Again => Key::Again,
+        Keycode::Undo => Key::Undo,
+        Keycode::Cut => Key::Cut,
+        Keycode::Copy => Key::Copy,
+        Keycode::Paste => Key::Paste,
+        Keycode::Find => Key::Find,
+        Keycode::Mute => Key::Mute,
+        Keycode::VolumeUp => Key::VolumeUp,
+        Keycode::VolumeDown => Key::VolumeDown,
+        Keycode::KpComma => Key::KpComma,
+        Keycode::KpEqualsAS400 => Key::KpEqualsAS400,
+        Keycode::AltErase => Key::AltErase,
+        Keycode::Sysreq => Key::SysReq,
+        Keycode::Cancel => Key::Cancel,
+        Keycode::Clear => Key::Clear,
+        Keycode::Prior => Key::Prior,
+        Keycode::Return2 => Key::Return2,
+        Keycode::Separator => Key::Separator,
+        Keycode::Out => Key::Out,
+        Keycode::Oper => Key::Oper,
+        Keycode::ClearAgain => Key::ClearAgain,
+        Keycode::CrSel => Key::CrSel,
+        Keycode::ExSel => Key::ExSel,
+        Keycode::Kp00 => Key::Kp00,
+        Keycode::Kp000 => Key::Kp000,
+        Keycode::ThousandsSeparator => Key::ThousandsSeparator,
+        Keycode::DecimalSeparator => Key::DecimalSeparator,
+        Keycode::CurrencyUnit => Key::CurrencyUnit,
+        Keycode::CurrencySubUnit => Key::CurrencySubUnit,
+        Keycode::KpLeftParen => Key::KpLeftParen,
+        Keycode::KpRightParen => Key::KpRightParen,
+        Keycode::KpLeftBrace => Key::KpLeftBrace,
+        Keycode::KpRightBrace => Key::KpRightBrace,
+        Keycode::KpTab => Key::KpTab,
+        Keycode::KpBackspace => Key::KpBackspace,
+        Keycode::KpA => Key::KpA,
+        Keycode::KpB => Key::KpB,
+        Keycode::KpC => Key::KpC,
+        Keycode::KpD => Key::KpD,
+        Keycode::KpE => Key::KpE,
+        Keycode::KpF => Key::KpF,
+        Keycode::KpXor => Key::KpXor,
+        Keycode::KpPower => Key::KpPower,
+        Keycode::KpPercent => Key::KpPercent,
+        Keycode::KpLess => Key::KpLess,
+        Keycode::KpGreater => Key::KpGreater,
+        Keycode::KpAmpersand => Key::KpAmpersand,
+        Keycode::KpDblAmpersand => Key::KpDblAmpersand,
+        Keycode::KpVerticalBar => Key::KpVerticalBar,
+        Keycode::KpDblVerticalBar => Key::KpDblVerticalBar,
+        Keycode::KpColon => Key::KpColon,
+        Keycode::KpHash => Key::KpHash,
+        Keycode::KpSpace => Key::KpSpace,
+        Keycode::KpAt => Key::KpAt,
+        Keycode::KpExclam => Key::KpExclam,
+        Keycode::KpMemStore => Key::KpMemStore,
+        Keycode::KpMemRecall => Key::KpMemRecall,
+        Keycode::KpMemClear => Key::KpMemClear,
+        Keycode::KpMemAdd => Key::KpMemAdd,
+        Keycode::KpMemSubtract => Key::KpMemSubtract,
+        Keycode::KpMemMultiply => Key::KpMemMultiply,
+        Keycode::KpMemDivide => Key::KpMemDivide,
+        Keycode::KpPlusMinus => Key::KpPlusMinus,
+        Keycode::KpClear => Key::KpClear,
+        Keycode::KpClearEntry => Key::KpClearEntry,
+        Keycode::KpBinary => Key::KpBinary,
+        Keycode::KpOctal => Key::KpOctal,
+        Keycode::KpDecimal => Key::KpDecimal,
+        Keycode::KpHexadecimal => Key::KpHexadecimal,
+        Keycode::LCtrl => Key::LControl,
+        Keycode::LShift => Key::LShift,
+        Keycode::LAlt => Key::LOpt,
+        Keycode::LGui => Key::LGui,
+        Keycode::RCtrl => Key::RControl,
+        Keycode::RShift => Key::RShift,
+        Keycode::RAlt => Key::ROpt,
+        Keycode::RGui => Key::RGui,
+        Keycode::Mode => Key::Mode,
+        Keycode::AudioNext => Key::AudioNext,
+        Keycode::AudioPrev => Key::AudioPrev,
+        Keycode::AudioStop => Key::AudioStop,
+        Keycode::AudioPlay => Key::AudioPlay,
+        Keycode::AudioMute => Key::AudioMute,
+        Keycode::MediaSelect => Key::MediaSelect,
+        Keycode::Www => Key::Www,
+        Keycode::Mail => Key::Mail,
+        Keycode::Calculator => Key::Calculator,
+        Keycode::Computer => Key::Computer,
+        Keycode::AcSearch => Key::AcSearch,
+        Keycode::AcHome => Key::AcHome,
+        Keycode::AcBack => Key::AcBack,
+        Keycode::AcForward => Key::AcForward,
+        Keycode::AcStop => Key::AcStop,
+        Keycode::AcRefresh => Key::AcRefresh,
+        Keycode::AcBookmarks => Key::AcBookmarks,
+        Keycode::BrightnessDown => Key::BrightnessDown,
+        Keycode::BrightnessUp => Key::BrightnessUp,
+        Keycode::DisplaySwitch => Key::DisplaySwitch,
+        Keycode::KbdIllumToggle => Key::KbdIllumToggle,
+        Keycode::KbdIllumDown => Key::KbdIllumDown,
+        Keycode::KbdIllumUp => Key::KbdIllumUp,
+        Keycode::Eject => Key::Eject,
+        Keycode::Sleep => Key::Sleep
+    }
+}
+
+fn sdl_to_scancode(k: Scancode) -> Key {
+    match k {
+        Scancode::A => Key::A,
+        Scancode::B => Key::B,
+        Scancode::C => Key::C,
+        Scancode::D => Key::D,
+        Scancode::E => Key::E,
+        Scancode::F => Key::F,
+        Scancode::G => Key::G,
+        Scancode::H => Key::H,
+        Scancode::I => Key::I,
+        Scancode::J => Key::J,
+        Scancode::K => Key::K,
+        Scancode::L => Key::L,
+        Scancode::M => Key::M,
+        Scancode::N => Key::N,
+        Scancode::O => Key::O,
+        Scancode::P => Key::P,
+        Scancode::Q => Key::Q,
+        Scancode::R => Key::R,
+        Scancode::S => Key::S,
+        Scancode::T => Key::T,
+        Scancode::U => Key::U,
+        Scancode::V => Key::V,
+        Scancode::W => Key::W,
+        Scancode::X => Key::X,
+        Scancode::Y => Key::Y,
+        Scancode::Z => Key::Z,
+        Scancode::Num1 => Key::One,
+        Scancode::Num2 => Key::Two,
+        Scancode::Num3 => Key::Three,
+        Scancode::Num4 => Key::Four,
+        Scancode::Num5 => Key::Five,
+        Scancode::Num6 => Key::Six,
+        Scancode::Num7 => Key::Seven,
+        Scancode::Num8 => Key::Eight,
+        Scancode::Num9 => Key::Nine,
+        Scancode::Num0 => Key::Zero,
+        Scancode::Return => Key::Return,
+        Scancode::Escape => Key::Escape,
+        Scancode::Backspace => Key::Backspace,
+        Scancode::Tab => Key::Tab,
+        Scancode::Space => Key::Space,
+        Scancode::Minus => Key::Minus,
+        Scancode::Equals => Key::Equals,
+        Scancode::LeftBracket => Key::OpenBracket,
+        Scancode::RightBracket => Key::CloseBracket,
+        Scancode::Backslash => Key::Backslash,
+        Scancode::NonUsHash => Key::NonUsHash,
+        Scancode::Semicolon => Key::Semicolon,
+        Scancode::Apostrophe => Key::Apostrophe,
+        Scancode::Grave => Key::Backtick,
+        Scancode::Comma => Key::Comma,
+        Scancode::Period => Key::Dot,
+        Scancode::Slash => Key::Slash,
+        Scancode::CapsLock => Key::CapsLock,
+        Scancode::F1 => Key::F1,
+        Scancode::F2 => Key::F2,
+        Scancode::F3 => Key::F3,
+        Scancode::F4 => Key::F4,
+        Scancode::F5 => Key::F5,
+        Scancode::F6 => Key::F6,
+        Scancode::F7 => Key::F7,
+        Scancode::F8 => Key::F8,
+        Scancode::F9 => Key::F9,
+        Scancode::F10 => Key::F10,
+        Scancode::F11 => Key::F11,
+        Scancode::F12 => Key::F12,
+        Scancode::PrintScreen => Key::PrintScreen,
+        Scancode::ScrollLock => Key::ScrollLock,
+        Scancode::Pause => Key::Pause,
+        Scancode::Insert => Key::Insert,
+        Scancode::Home => Key::Home,
+        Scancode::PageUp => Key::PageUp,
+        Scancode::Delete => Key::Delete,
+        Scancode::End => Key::End,
+        Scancode::PageDown => Key::PageDown,
+        Scancode::Right => Key::Right,
+        Scancode::Left => Key::Left,
+        Scancode::Down => Key::Down,
+        Scancode::Up => Key::Up,
+        Scancode::NumLockClear => Key::NumLockClear,
+        Scancode::KpDivide => Key::KpDivide,
+        Scancode::KpMultiply => Key::KpMultiply,
+        Scancode::KpMinus => Key::KpMinus,
+        Scancode::KpPlus => Key::KpPlus,
+        Scancode::KpEnter => Key::KpEnter,
+        Scancode::Kp1 => Key::Kp1,
+        Scancode::Kp2 => Key::Kp2,
+        Scancode::Kp3 => Key::Kp3,
+        Scancode::Kp4 => Key::Kp4,
+        Scancode::Kp5 => Key::Kp5,
+        Scancode::Kp6 => Key::Kp6,
+        Scancode::Kp7 => Key::Kp7,
+        Scancode::Kp8 => Key::Kp8,
+        Scancode::Kp9 => Key::Kp9,
+        Scancode::Kp0 => Key::Kp0,
+        Scancode::KpPeriod => Key::KpPeriod,
+        Scancode::NonUsBackslash => Key::NonUsBackslash,
+        Scancode::Application => Key::Application,
+        Scancode::Power => Key::Power,
+        Scancode::KpEquals => Key::KpEquals,
+        Scancode::F13 => Key::F13,
+        Scancode::F14 => Key::F14,
+        Scancode::F15 => Key::F15,
+        Scancode::F16 => Key::F16,
+        Scancode::F17 => Key::F17,
+        Scancode::F18 => Key::F18,
+        Scancode::F19 => Key::F19,
+        Scancode::F20 => Key::F20,
+        Scancode::F21 => Key::F21,
+        Scancode::F22 => Key::F22,
+        Scancode::F23 => Key::F23,
+        Scancode::F24 => Key::F24,
+        Scancode::Execute => Key::Execute,
+        Scancode::Help => Key::Help,
+        Scancode::Menu => Key::Menu,
+        Scancode::Select => Key::Select,
+        Scancode::Stop => Key::Stop,
+        Scancode::Again => Key::Again,
+        Scancode::Undo => Key::Undo,
+        Scancode::Cut => Key::Cut,
+        Scancode::Copy => Key::Copy,
+        Scancode::Paste => Key::Paste,
+        Scancode::Find => Key::Find,
+        Scancode::Mute => Key::Mute,
+        Scancode::VolumeUp => Key::VolumeUp,
+        Scancode::VolumeDown => Key::VolumeDown,
+        Scancode::KpComma => Key::KpComma,
+        Scancode::KpEqualsAS400 => Key::KpEqualsAS400,
+        Scancode::International1 => Key::International1,
+        Scancode::International2 => Key::International2,
+        Scancode::International3 => Key::International3,
+        Scancode::International4 => Key::International4,
+        Scancode::International5 => Key::International5,
+        Scancode::International6 => Key::International6,
+        Scancode::International7 => Key::International7,
+        Scancode::International8 => Key::International8,
+        Scancode::International9 => Key::International9,
+        Scancode::Lang1 => Key::Lang1,
+        Scancode::Lang2 => Key::Lang2,
+        Scancode::Lang3 => Key::Lang3,
+        Scancode::Lang4 => Key::Lang4,
+        Scancode::Lang5 => Key::Lang5,
+        Scancode::Lang6 => Key::Lang6,
+        Scancode::Lang7 => Key::Lang7,
+        Scancode::Lang8 => Key::Lang8,
+        Scancode::Lang9 => Key::Lang9,
+        Scancode::AltErase => Key::AltErase,
+        Scancode::SysReq => Key::SysReq,
+        Scancode::Cancel => Key::Cancel,
+        Scancode::Clear => Key::Clear,
+        Scancode::Prior => Key::Prior,
+        Scancode::Return2 => Key::Return2,
+        Scancode::Separator => Key::Separator,
+        Scancode::Out => Key::Out,
+        Scancode::Oper => Key::Oper,
+        Scancode::ClearAgain => Key::ClearAgain,
+        Scancode::CrSel => Key::CrSel,
+        Scancode::ExSel => Key::ExSel,
+        Scancode::Kp00 => Key::Kp00,
+        Scancode::Kp000 => Key::Kp000,
+        Scancode::ThousandsSeparator => Key::ThousandsSeparator,
+        Scancode::DecimalSeparator => Key::DecimalSeparator,
+        Scancode::CurrencyUnit => Key::CurrencyUnit,
+        Scancode::CurrencySubUnit => Key::CurrencySubUnit,
+        Scancode::KpLeftParen => Key::KpLeftParen,
+        Scancode::KpRightParen => Key::KpRightParen,
+        Scancode::KpLeftBrace => Key::KpLeftBrace,
+        Scancode::KpRightBrace => Key::KpRightBrace,
+        Scancode::KpTab => Key::KpTab,
+        Scancode::KpBackspace => Key::KpBackspace,
+        Scancode::KpA => Key::KpA,
+        Scancode::KpB => Key::KpB,
+        Scancode::KpC => Key::KpC,
+        Scancode::KpD => Key::KpD,
+        Scancode::KpE => Key::KpE,
+        Scancode::KpF => Key::KpF,
+        Scancode::KpXor => Key::KpXor,
+        Scancode::KpPower => Key::KpPower,
+        Scancode::KpPercent => Key::KpPercent,
+        Scancode::KpLess => Key::KpLess,
+        Scancode::KpGreater => Key::KpGreater,
+        Scancode::KpAmpersand => Key::KpAmpersand,
+        Scancode::KpDblAmpersand => Key::KpDblAmpersand,
+        Scancode::KpVerticalBar => Key::KpVerticalBar,
+        Scancode::KpDblVerticalBar => Key::KpDblVerticalBar,
+        Scancode::KpColon => Key::KpColon,
+        Scancode::KpHash => Key::KpHash,
+        Scancode::KpSpace => Key::KpSpace,
+        Scancode::KpAt => Key::KpAt,
+        Scancode::KpExclam => Key::KpExclam,
+        Scancode::KpMemStore => Key::KpMemStore,
+        Scancode::KpMemRecall => Key::KpMemRecall,
+        Scancode::KpMemClear => Key::KpMemClear,
+        Scancode::KpMemAdd => Key::KpMemAdd,
+        Scancode::KpMemSubtract => Key::KpMemSubtract,
+        Scancode::KpMemMultiply => Key::KpMemMultiply,
+        Scancode::KpMemDivide => Key::KpMemDivide,
+        Scancode::KpPlusMinus => Key::KpPlusMinus,
+        Scancode::KpClear => Key::KpClear,
+        Scancode::KpClearEntry => Key::KpClearEntry,
+        Scancode::KpBinary => Key::KpBinary,
+        Scancode::KpOctal => Key::KpOctal,
+        Scancode::KpDecimal => Key::KpDecimal,
+        Scancode::KpHexadecimal => Key::KpHexadecimal,
+        Scancode::LCtrl => Key::LControl,
+        Scancode::LShift => Key::LShift,
+        Scancode::LAlt => Key::LOpt,
+        Scancode::LGui => Key::LGui,
+        Scancode::RCtrl => Key::RControl,
+        Scancode::RShift => Key::RShift,
+        Scancode::RAlt => Key::ROpt,
+        Scancode::RGui => Key::RGui,
+        Scancode::Mode => Key::Mode,
+        Scancode::AudioNext => Key::AudioNext,
+        Scancode::AudioPrev => Key::AudioPrev,
+        Scancode::AudioStop => Key::AudioStop,
+        Scancode::AudioPlay => Key::AudioPlay,
+        Scancode::AudioMute => Key::AudioMute,
+        Scancode::MediaSelect => Key::MediaSelect,
+        Scancode::Www => Key::Www,
+        Scancode::Mail => Key::Mail,
+        Scancode::Calculator => Key::Calculator,
+        Scancode::Computer => Key::Computer,
+        Scancode::AcSearch => Key::AcSearch,
+        Scancode::AcHome => Key::AcHome,
+        Scancode::AcBack => Key::AcBack,
+        Scancode::AcForward => Key::AcForward,
+        Scancode::AcStop => Key::AcStop,
+        Scancode::AcRefresh => Key::AcRefresh,
+        Scancode::AcBookmarks => Key::AcBookmarks,
+        Scancode::BrightnessDown => Key::BrightnessDown,
+        Scancode::BrightnessUp => Key::BrightnessUp,
+        Scancode::DisplaySwitch => Key::DisplaySwitch,
+        Scancode::KbdIllumToggle => Key::KbdIllumToggle,
+        Scancode::KbdIllumDown => Key::KbdIllumDown,
+        Scancode::KbdIllumUp => Key::KbdIllumUp,
+        Scancode::Eject => Key::Eject,
+        Scancode::Sleep => Key::Sleep,
+        Scancode::App1 => Key::App1,
+        Scancode::App2 => Key::App2,
         _ => Key::Unknown
     }
 }
 
+fn sdl_to_modifiers(m: Mod) -> Modifiers {
+    Modifiers {
+        left_shift: m.contains(Mod::LSHIFTMOD),
+        right_shift: m.contains(Mod::RSHIFTMOD),
+        left_control: m.contains(Mod::LCTRLMOD),
+        right_control: m.contains(Mod::RCTRLMOD),
+        left_opt: m.contains(Mod::LALTMOD),
+        right_opt: m.contains(Mod::RALTMOD),
+        left_gui: m.contains(Mod::LGUIMOD),
+        right_gui: m.contains(Mod::RGUIMOD),
+        caps_lock: m.contains(Mod::CAPSMOD),
+        num_lock: m.contains(Mod::NUMMOD)
+    }
+}
+
+fn sdl_to_mouse_button(b: SDLMouseButton) -> MouseButton {
+    match b {
+        SDLMouseButton::Left => MouseButton::Left,
+        SDLMouseButton::Middle => MouseButton::Middle,
+        SDLMouseButton::Right => MouseButton::Right,
+        SDLMouseButton::X1 => MouseButton::X1,
+        SDLMouseButton::X2 => MouseButton::X2,
+        _ => MouseButton::Unknown
+    }
+}
+
+// Touch positions are normalised to the window
+fn touch_to_screen(x: f32, y: f32, camera: &CameraRes) -> Vector2<i32> {
+    Vector2::new((x * camera.screen.x as f32) as i32, (y * camera.screen.y as f32) as i32)
+}
+
+fn sdl_to_gamepad_button(b: SDLButton) -> GamepadButton {
+    match b {
+        SDLButton::A => GamepadButton::A,
+        SDLButton::B => GamepadButton::B,
+        SDLButton::X => GamepadButton::X,
+        SDLButton::Y => GamepadButton::Y,
+        SDLButton::Back => GamepadButton::Back,
+        SDLButton::Guide => GamepadButton::Guide,
+        SDLButton::Start => GamepadButton::Start,
+        SDLButton::LeftStick => GamepadButton::LeftStick,
+        SDLButton::RightStick => GamepadButton::RightStick,
+        SDLButton::LeftShoulder => GamepadButton::LeftShoulder,
+        SDLButton::RightShoulder => GamepadButton::RightShoulder,
+        SDLButton::DPadUp => GamepadButton::DPadUp,
+        SDLButton::DPadDown => GamepadButton::DPadDown,
+        SDLButton::DPadLeft => GamepadButton::DPadLeft,
+        SDLButton::DPadRight => GamepadButton::DPadRight
+    }
+}
+
+fn sdl_to_gamepad_axis(a: SDLAxis) -> GamepadAxis {
+    match a {
+        SDLAxis::LeftX => GamepadAxis::LeftX,
+        SDLAxis::LeftY => GamepadAxis::LeftY,
+        SDLAxis::RightX => GamepadAxis::RightX,
+        SDLAxis::RightY => GamepadAxis::RightY,
+        SDLAxis::TriggerLeft => GamepadAxis::TriggerLeft,
+        SDLAxis::TriggerRight => GamepadAxis::TriggerRight
+    }
+}
+
 struct SDLContext {
     events: EventPump,
+    controller: GameControllerSubsystem,
+    // Open controllers by their joystick instance id, which is what the events refer to them by
+    controllers: HashMap<u32, (usize, GameController)>,
 }
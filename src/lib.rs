@@ -32,27 +32,42 @@ pub mod ecs {
 }
 
 use std::time::Instant;
+use std::path::Path;
+use std::collections::HashMap;
 
 use specs::*;
 
 use misc::*;
 use misc::persist::*;
+use misc::locale::*;
 use render::*;
 use render::sdl::*;
 use physics::*;
 use net::*;
 use net::imp::*;
 use input::*;
+use input::action::*;
+use input::record::*;
 use input::sdl::*;
 use sound::*;
 use sound::imp::*;
 
 const TARGET_FRAME_RATE: f32 = 60.0;
 const TARGET_FRAME_TIME: f32 = 1.0 / TARGET_FRAME_RATE;
+const THEME_EXTENSION: &str = "mth";
 
 pub struct InvaderBuilder<'a, 'b> {
     dispatcher: DispatcherBuilder<'a, 'b>,
     stage: Option<String>,
+    ui: Option<String>,
+    focus_highlight: Option<String>,
+    theme: UITheme,
+    ui_scale: UIScaleMode,
+    locales: HashMap<String, String>,
+    locale: Option<String>,
+    input_map: InputMap,
+    record_input: Option<String>,
+    replay_input: Option<String>,
     render: SDLRenderImpl<'b>,
     input: SDLInputImpl
 }
@@ -64,6 +79,15 @@ impl<'a, 'b> InvaderBuilder<'a, 'b> {
         Self {
             dispatcher: DispatcherBuilder::new(),
             stage: None,
+            ui: None,
+            focus_highlight: None,
+            theme: UITheme::new(),
+            ui_scale: UIScaleMode::Manual(1.0),
+            locales: HashMap::new(),
+            locale: None,
+            input_map: InputMap::new(),
+            record_input: None,
+            replay_input: None,
             render: SDLRenderImpl::init(&sdl_context, Vector::new(800.0, 600.0).convert()),
             input: SDLInputImpl::init(&sdl_context)
         }
@@ -79,6 +103,49 @@ impl<'a, 'b> InvaderBuilder<'a, 'b> {
         self
     }
 
+    pub fn set_ui(mut self, ui: &str) -> Self {
+        self.ui = Some(ui.to_string());
+        self
+    }
+
+    pub fn set_focus_highlight(mut self, sprite: &str) -> Self {
+        self.focus_highlight = Some(sprite.to_string());
+        self
+    }
+
+    pub fn set_ui_scale(mut self, mode: UIScaleMode) -> Self {
+        self.ui_scale = mode;
+        self
+    }
+
+    pub fn add_locale(mut self, locale: &str, path: &str) -> Self {
+        self.locales.insert(locale.to_string(), path.to_string());
+        self
+    }
+
+    pub fn set_locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_string());
+        self
+    }
+
+    pub fn set_input_map(mut self, path: &str) -> Self {
+        match InputMap::from_file(path) {
+            Some(input_map) => self.input_map = input_map,
+            None => eprintln!("could not load input map \"{}\"", path)
+        }
+        self
+    }
+
+    pub fn record_input(mut self, path: &str) -> Self {
+        self.record_input = Some(path.to_string());
+        self
+    }
+
+    pub fn replay_input(mut self, path: &str) -> Self {
+        self.replay_input = Some(path.to_string());
+        self
+    }
+
     pub fn add_sprite(mut self, key: &str, path: &str) -> Self {
         self.render.add_sprite(key, path);
         self
@@ -101,6 +168,19 @@ impl<'a, 'b> InvaderBuilder<'a, 'b> {
             },
             None => eprintln!("could not load sprite sheet \"{}\"", path)
         }
+
+        // A theme with the same name as the sprite sheet is loaded along with it
+        if let Some(theme) = Path::new(path).with_extension(THEME_EXTENSION).to_str().and_then(UITheme::from_file) {
+            self.theme.extend(theme);
+        }
+        self
+    }
+
+    pub fn add_theme(mut self, path: &str) -> Self {
+        match UITheme::from_file(path) {
+            Some(theme) => self.theme.extend(theme),
+            None => eprintln!("could not load theme \"{}\"", path)
+        }
         self
     }
 
@@ -111,9 +191,11 @@ impl<'a, 'b> InvaderBuilder<'a, 'b> {
         let physics_sys = PhysicsSys::new();
         let sound_sys = SoundSys::new(SoundImp::new());
         let persist_sys = PersistSys::new();
+        let locale_sys = LocaleSys::new();
 
         let mut dispatcher = self.dispatcher
             .with(persist_sys, "perist", &[])
+            .with(locale_sys, "locale", &[])
             .with(network_sys, "network_sync", &[])
             .with(physics_sys, "physics", &["network_sync"])
             .with_thread_local(input_sys)
@@ -127,10 +209,27 @@ impl<'a, 'b> InvaderBuilder<'a, 'b> {
         dispatcher.setup(&mut world);
 
         world.insert(PhysicsRes::new());
+        world.write_resource::<UIFocusRes>().highlight = self.focus_highlight;
+        world.insert(self.theme);
+        world.write_resource::<UIScaleRes>().mode = self.ui_scale;
+        world.write_resource::<LocaleRes>().files = self.locales;
+        world.insert(self.input_map);
+        if let Some(path) = self.record_input {
+            world.write_resource::<InputRecorderRes>().start_recording(&path);
+        }
+        if let Some(path) = self.replay_input {
+            world.write_resource::<InputRecorderRes>().start_replay(&path);
+        }
+        if let Some(locale) = self.locale {
+            world.write_resource::<LocaleRequestQueue>().push(LocaleRequest::SetLocale(locale));
+        }
 
         if let Some(stage) = self.stage {
             world.write_resource::<PersistRequestQueue>().push(PersistRequest::LoadStage(stage));
         }
+        if let Some(ui) = self.ui {
+            world.write_resource::<PersistRequestQueue>().push(PersistRequest::LoadUI(ui));
+        }
 
         Invader {
             world,
@@ -164,6 +263,11 @@ impl<'a, 'b> Invader<'a, 'b> {
                 delta_time = 0.0;
             }
 
+            // Replays use the frame times they were recorded with
+            if let Some(recorded) = self.world.read_resource::<InputRecorderRes>().delta_time() {
+                delta_time = recorded;
+            }
+
             self.world.write_resource::<PhysicsRes>().delta_time = delta_time;
 
             // Run the game
@@ -11,7 +11,8 @@ use nalgebra::Vector2;
 
 use specs::*;
 
-use crate::misc::persist::{StageMarker, StageMarkerAllocator, PersistRequestQueue};
+use crate::misc::persist::{StageMarker, StageMarkerAllocator, UIMarker, UIMarkerAllocator, PersistRequestQueue};
+use crate::misc::locale::{LocaleRes, LocalisedTextCom};
 
 #[derive(Default)]
 pub struct StateRes(HashMap<String, Box<dyn State>>);
@@ -65,9 +66,10 @@ impl<T: NumCast + Debug + Copy + PartialEq + 'static, U: NumCast + Debug + Copy
 
 #[macro_export]
 macro_rules! event_queue {
-    ($queue_name:ident : pub $type:tt $name:ident {$($element:tt)*}) => {
+    ($(#[$meta:meta])* $queue_name:ident : pub $type:tt $name:ident {$($element:tt)*}) => {
         #[allow(dead_code)]
         #[derive(Clone, Debug)]
+        $(#[$meta])*
         pub $type $name {
             $($element)+
         }
@@ -179,7 +181,12 @@ pub fn register(world: &mut World) {
     world.insert(StateRes::new());
     world.insert(StageMarkerAllocator::new());
     world.register::<StageMarker>();
+    world.insert(UIMarkerAllocator::new());
+    world.register::<UIMarker>();
     world.insert(PersistRequestQueue::new());
+    world.insert(LocaleRes::new());
+    world.register::<LocalisedTextCom>();
 }
 
 pub mod persist;
+pub mod locale;
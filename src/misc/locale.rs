@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use specs::*;
+
+use invader_macro::DefaultConstructor;
+use crate::render::{TextCom, TextUICom};
+
+event_queue! {
+    LocaleRequestQueue: pub enum LocaleRequest {
+        SetLocale(String)
+    }
+}
+
+#[derive(DefaultConstructor)]
+pub struct LocaleSys;
+
+impl<'a> System<'a> for LocaleSys {
+    type SystemData = (specs::Write<'a, LocaleRequestQueue>,
+        specs::Write<'a, LocaleRes>,
+        ReadStorage<'a, LocalisedTextCom>,
+        WriteStorage<'a, TextUICom>,
+        WriteStorage<'a, TextCom>);
+
+    fn run(&mut self, (mut requests, mut locale, localised, mut text_labels, mut texts): Self::SystemData) {
+        for request in requests.iter() {
+            match request {
+                LocaleRequest::SetLocale(name) => {
+                    match locale.files.get(name).and_then(|file| StringTable::from_file(file)) {
+                        Some(table) => {
+                            locale.locale = name.clone();
+                            locale.table = table;
+                        },
+                        None => eprintln!("could not load locale \"{}\"", name)
+                    }
+                }
+            }
+        }
+        requests.clear();
+
+        // Texts are translated every frame, so changes to the locale or the arguments show up right away
+        for (localised, text) in (&localised, &mut text_labels).join() {
+            relocalise(&mut text.text, locale.translate(&localised.key, &localised.args));
+        }
+        for (localised, text) in (&localised, &mut texts).join() {
+            relocalise(&mut text.text, locale.translate(&localised.key, &localised.args));
+        }
+    }
+}
+
+fn relocalise(target: &mut String, text: String) {
+    if *target != text {
+        *target = text;
+    }
+}
+
+pub struct LocaleRes {
+    pub locale: String,
+    pub files: HashMap<String, String>,
+    table: StringTable
+}
+
+impl LocaleRes {
+    pub fn new() -> Self {
+        Self {
+            locale: String::new(),
+            files: HashMap::new(),
+            table: StringTable::new()
+        }
+    }
+
+    // Replaces {0}, {1}, ... in the string with the arguments, keys without a translation are shown as they are
+    pub fn translate(&self, key: &str, args: &[String]) -> String {
+        let template = self.table.strings.get(key).map_or(key, |s| s.as_str());
+
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('}').and_then(|end| after[..end].parse::<usize>().ok().map(|i| (end, i))) {
+                Some((end, i)) if i < args.len() => {
+                    text.push_str(&args[i]);
+                    rest = &after[end + 1..];
+                },
+                _ => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+
+    pub fn font_fallbacks(&self) -> HashMap<String, String> {
+        self.table.fonts.clone()
+    }
+}
+
+impl Default for LocaleRes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sets the text of the TextUICom or TextCom of the entity to the translation of the key
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct LocalisedTextCom {
+    pub key: String,
+    pub args: Vec<String>
+}
+
+impl LocalisedTextCom {
+    pub fn new(key: &str, args: &[&str]) -> Self {
+        Self {
+            key: key.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StringTable {
+    pub strings: HashMap<String, String>,
+    pub fonts: HashMap<String, String>
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self {
+            strings: HashMap::new(),
+            fonts: HashMap::new()
+        }
+    }
+
+    pub fn add_string(&mut self, key: &str, text: &str) {
+        self.strings.insert(key.to_string(), text.to_string());
+    }
+
+    pub fn remove_string(&mut self, key: &str) {
+        self.strings.remove(key);
+    }
+
+    // The fallback is used for texts with glyphs that the font does not provide
+    pub fn add_font_fallback(&mut self, font: &str, fallback: &str) {
+        self.fonts.insert(font.to_string(), fallback.to_string());
+    }
+
+    pub fn remove_font_fallback(&mut self, font: &str) {
+        self.fonts.remove(font);
+    }
+
+    pub fn into_file(&self, file: &str) {
+        let written = File::create(file).map_err(|e| e.to_string())
+            .and_then(|f| bincode::serialize_into(&f, &self).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            eprintln!("could not save string table \"{}\": {}", file, e);
+        }
+    }
+
+    // Missing and malformed files both give None, the LocaleSys reports the locale that couldn't be loaded
+    pub fn from_file(file: &str) -> Option<Self> {
+        let file = File::open(file).ok()?;
+        bincode::deserialize_from(&file).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LocaleRes, StringTable};
+
+    fn locale() -> LocaleRes {
+        let mut locale = LocaleRes::new();
+        locale.table.add_string("greeting", "Hello {0}, you have {1} messages");
+        locale.table.add_string("braces", "{} {x} {2} {0");
+        locale
+    }
+
+    #[test]
+    fn translate_replaces_arguments() {
+        let args = vec!["Ann".to_string(), "3".to_string()];
+        assert_eq!(locale().translate("greeting", &args), "Hello Ann, you have 3 messages");
+    }
+
+    #[test]
+    fn translate_keeps_unknown_keys() {
+        assert_eq!(locale().translate("missing.key", &[]), "missing.key");
+    }
+
+    #[test]
+    fn translate_keeps_invalid_placeholders() {
+        let args = vec!["a".to_string()];
+        assert_eq!(locale().translate("braces", &args), "{} {x} {2} {0");
+        assert_eq!(locale().translate("greeting", &args), "Hello a, you have {1} messages");
+    }
+
+    #[test]
+    fn malformed_string_table_is_not_loaded() {
+        let path = std::env::temp_dir().join(format!("invader-locale-{}.mlc", std::process::id()));
+        let path = path.to_str().unwrap();
+        locale().table.into_file(path);
+        assert_eq!(StringTable::from_file(path).unwrap().strings.len(), 2);
+
+        std::fs::write(path, [0xff, 0xff, 0xff]).unwrap();
+        assert!(StringTable::from_file(path).is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+}
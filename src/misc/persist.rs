@@ -1,24 +1,31 @@
 use std::fs::File;
 use std::collections::HashMap;
+use std::convert::TryInto;
 
 use serde::{Serialize, Deserialize};
 
 use nphysics2d::object::*;
 use ncollide2d::shape::*;
+use ncollide2d::pipeline::object::CollisionGroups;
+use nphysics2d::material::{MaterialHandle, BasicMaterial};
 use nalgebra::geometry::*;
+use nalgebra::{DVector, Vector2, Point2};
 
 use specs::*;
 use specs::saveload::*;
 
 use invader_macro::DefaultConstructor;
-use crate::physics::{TransformCom, RigidBodyCom, ColliderCom, PhysicsRes};
-use crate::render::{SpriteCom, TextCom};
+use crate::physics::{TransformCom, RigidBodyCom, ColliderCom, PhysicsRes, RigidBodyBuilder, BodyProperties};
+use crate::render::{SpriteCom, TextCom, ConstraintCom, UIEdges, UIParentCom, GroupUICom, ScrollUICom, TextUICom, ButtonUICom, TextFieldUICom, CheckboxUICom, SliderUICom, DropdownUICom, ProgressBarUICom, ImageUICom, StyleUICom};
 use crate::misc::Vector;
+use crate::misc::locale::LocalisedTextCom;
 
 event_queue! {
     PersistRequestQueue: pub enum PersistRequest {
         SaveStage(String),
-        LoadStage(String)
+        LoadStage(String),
+        LoadUI(String),
+        UnloadUI
     }
 }
 
@@ -35,41 +42,48 @@ impl<'a> System<'a> for PersistSys {
         WriteStorage<'a, SpriteCom>,
         WriteStorage<'a, TextCom>,
         WriteStorage<'a, RigidBodyCom>,
-        WriteStorage<'a, ColliderCom>);
+        WriteStorage<'a, ColliderCom>,
+        WriteStorage<'a, LocalisedTextCom>,
+        UIStorage<'a>);
 
-    fn run(&mut self, (entities, mut requests, mut stage_markers, mut stage_marker_alloc, mut physics, mut transforms, mut sprites, mut texts, mut bodies, mut colliders): Self::SystemData) {
+    fn run(&mut self, (entities, mut requests, mut stage_markers, mut stage_marker_alloc, mut physics, mut transforms, mut sprites, mut texts, mut bodies, mut colliders, mut localised, mut ui): Self::SystemData) {
         for request in requests.iter() {
             match request {
                 PersistRequest::SaveStage(file) => {
-                    let file = File::create(file).unwrap();
-
                     let mut elements: Vec<StageEntity> = Vec::new();
-                    for (_marker, transform, sprite, text, body, collider) in (&stage_markers, (&transforms).maybe(), (&sprites).maybe(), (&texts).maybe(), (&bodies).maybe(), (&colliders).maybe()).join() {
+                    for (_marker, transform, sprite, text, localisation, body, collider) in (&stage_markers, (&transforms).maybe(), (&sprites).maybe(), (&texts).maybe(), (&localised).maybe(), (&bodies).maybe(), (&colliders).maybe()).join() {
                         elements.push(StageEntity {
                             transform: transform.map(|c| c.clone()),
                             sprite: sprite.map(|c| c.clone()),
                             text: text.map(|c| c.clone()),
-                            body: body.map(|c| physics.read_rigid_body(c).unwrap().into()),
-                            collider: collider.map(|c| physics.read_collider(c).unwrap().into())
+                            localisation: localisation.map(|c| c.clone()),
+                            body: body.map(|c| PersistentRigidBody::new(physics.read_rigid_body(c).unwrap(), physics.body_properties(c))),
+                            colliders: collider.map_or(Vec::new(), |c| physics.read_colliders(c).filter_map(|c| {
+                                let collider = PersistentCollider::new(c);
+                                if collider.is_none() {
+                                    eprintln!("could not save collider with unknown shape to stage \"{}\"", file);
+                                }
+                                collider
+                            }).collect())
                         });
                     }
 
-                    bincode::serialize_into(&file, &elements).unwrap();
+                    if let Err(e) = stage_into_file(&elements, file) {
+                        eprintln!("could not save stage \"{}\": {}", file, e);
+                    }
                 },
                 PersistRequest::LoadStage(file) => {
-                    let file = match File::open(file) {
-                        Ok(file) => file,
-                        Err(_) => { println!("Could not open {}", file); continue; }
+                    let elements = match stage_from_file(file) {
+                        Ok(elements) => elements,
+                        Err(e) => { eprintln!("could not load stage \"{}\": {}", file, e); continue; }
                     };
 
-                    let elements: Vec<StageEntity> = bincode::deserialize_from(&file).unwrap();
-
                     for (_marker, entity) in (&stage_markers, &entities).join() {
                         transforms.remove(entity);
                         sprites.remove(entity);
-                        bodies.get(entity).map(|body| physics.bodies.remove(body.0));
+                        texts.remove(entity);
+                        localised.remove(entity);
                         bodies.remove(entity);
-                        colliders.get(entity).map(|collider| physics.colliders.remove(collider.0));
                         colliders.remove(entity);
                     }
                     stage_markers.clear();
@@ -88,20 +102,36 @@ impl<'a> System<'a> for PersistSys {
                         if let Some(text) = &element.text {
                             texts.insert(entity, text.clone()).unwrap();
                         }
+                        if let Some(localisation) = &element.localisation {
+                            localised.insert(entity, localisation.clone()).unwrap();
+                        }
                         if let Some(body) = &element.body {
-                            let com = physics.register_rigid_body(body.clone().into());
+                            let com = physics.build_rigid_body(body.clone().into());
                             rb = Some(com.0);
                             bodies.insert(entity, com).unwrap();
                         }
-                        if let Some(collider) = &element.collider {
-                            if let Some(rb) = rb {
-                                colliders.insert(entity, physics.register_collider(collider.clone().into_collider(&rb))).unwrap();
+                        if let Some(rb) = rb {
+                            if !element.colliders.is_empty() {
+                                let handles = element.colliders.iter().flat_map(|collider| physics.register_collider(collider.clone().into_collider(&rb)).0).collect();
+                                colliders.insert(entity, ColliderCom(handles)).unwrap();
                             }
                         }
 
                         stage_marker_alloc.mark(entity, &mut stage_markers);
                     }
-                }
+                },
+                PersistRequest::LoadUI(file) => {
+                    let layout = match UILayout::from_file(file) {
+                        Some(layout) => layout,
+                        None => { eprintln!("could not load ui layout \"{}\"", file); continue; }
+                    };
+
+                    unload_ui(&entities, &mut ui);
+                    for (order, element) in layout.elements.iter().enumerate() {
+                        spawn_ui(element, UIParentCom::root(order as i32), &entities, &mut ui, &mut localised);
+                    }
+                },
+                PersistRequest::UnloadUI => unload_ui(&entities, &mut ui)
             }
         }
         requests.clear();
@@ -112,13 +142,188 @@ pub struct StageMarkerType;
 pub type StageMarker = SimpleMarker<StageMarkerType>;
 pub type StageMarkerAllocator = SimpleMarkerAllocator<StageMarkerType>;
 
+pub struct UIMarkerType;
+pub type UIMarker = SimpleMarker<UIMarkerType>;
+pub type UIMarkerAllocator = SimpleMarkerAllocator<UIMarkerType>;
+
+type UIStorage<'a> = (WriteStorage<'a, UIMarker>,
+    specs::Write<'a, UIMarkerAllocator>,
+    WriteStorage<'a, ConstraintCom>,
+    WriteStorage<'a, UIParentCom>,
+    WriteStorage<'a, GroupUICom>,
+    WriteStorage<'a, ScrollUICom>,
+    WriteStorage<'a, TextUICom>,
+    WriteStorage<'a, ButtonUICom>,
+    WriteStorage<'a, TextFieldUICom>,
+    WriteStorage<'a, CheckboxUICom>,
+    WriteStorage<'a, SliderUICom>,
+    WriteStorage<'a, DropdownUICom>,
+    WriteStorage<'a, ProgressBarUICom>,
+    WriteStorage<'a, ImageUICom>,
+    WriteStorage<'a, StyleUICom>);
+
+fn unload_ui(entities: &Entities, ui: &mut UIStorage) {
+    // Game code may have deleted some of the elements already
+    for (entity, _marker) in (entities, &ui.0).join() {
+        entities.delete(entity).ok();
+    }
+    ui.0.clear();
+}
+
+// Creates the entity for an element of a layout file, followed by all of its children
+fn spawn_ui(element: &UIElement, parent: UIParentCom, entities: &Entities, ui: &mut UIStorage, localised: &mut WriteStorage<LocalisedTextCom>) {
+    let (markers, marker_alloc, constraints, parents, groups, scrolls, texts, buttons, text_fields, checkboxes, sliders, dropdowns, progress_bars, images, styles) = ui;
+    let entity = entities.create();
+
+    constraints.insert(entity, element.constraint.clone()).unwrap();
+    parents.insert(entity, parent).unwrap();
+    if let Some(style) = &element.style {
+        styles.insert(entity, style.clone()).unwrap();
+    }
+    if let Some(localisation) = &element.localisation {
+        localised.insert(entity, localisation.clone()).unwrap();
+    }
+    match &element.widget {
+        UIWidget::Empty => (),
+        UIWidget::Group { sprite, vertical, padding, spacing } => {
+            groups.insert(entity, GroupUICom::new(sprite, *vertical).with_padding(*padding).with_spacing(*spacing)).unwrap();
+        },
+        UIWidget::ScrollGroup { sprite, scrollbar, scrollbar_handle, vertical, padding, spacing } => {
+            groups.insert(entity, GroupUICom::new(sprite, *vertical).with_padding(*padding).with_spacing(*spacing)).unwrap();
+            scrolls.insert(entity, ScrollUICom::new(scrollbar, scrollbar_handle)).unwrap();
+        },
+        UIWidget::Text { text, font } => {
+            texts.insert(entity, TextUICom::new(text, font)).unwrap();
+        },
+        UIWidget::Button { sprite, sprite_hovered, sprite_pressed, sprite_disabled, element_name, disabled } => {
+            let mut button = ButtonUICom::new(sprite, sprite_hovered, sprite_pressed, sprite_disabled, element_name);
+            button.disabled = *disabled;
+            buttons.insert(entity, button).unwrap();
+        },
+        UIWidget::TextField { background, text, font, element_name, max_length, masked } => {
+            let mut text_field = TextFieldUICom::new(background, text, font, element_name);
+            text_field.max_length = *max_length;
+            text_field.masked = *masked;
+            text_fields.insert(entity, text_field).unwrap();
+        },
+        UIWidget::Checkbox { sprite, sprite_checked, element_name, checked } => {
+            checkboxes.insert(entity, CheckboxUICom::new(sprite, sprite_checked, element_name, *checked)).unwrap();
+        },
+        UIWidget::Slider { track, handle, element_name, min, max, step, value } => {
+            let mut slider = SliderUICom::new(track, handle, element_name, *min, *max, *value);
+            slider.step = *step;
+            slider.set_value(*value);
+            sliders.insert(entity, slider).unwrap();
+        },
+        UIWidget::Dropdown { background, list_background, font, element_name, options, selected } => {
+            let options: Vec<&str> = options.iter().map(|o| o.as_str()).collect();
+            dropdowns.insert(entity, DropdownUICom::new(background, list_background, font, element_name, &options, *selected)).unwrap();
+        },
+        UIWidget::ProgressBar { background, fill, progress } => {
+            progress_bars.insert(entity, ProgressBarUICom::new(background, fill, *progress)).unwrap();
+        },
+        UIWidget::Image { sprite } => {
+            images.insert(entity, ImageUICom::new(sprite)).unwrap();
+        }
+    }
+    marker_alloc.mark(entity, markers);
+
+    for (order, child) in element.children.iter().enumerate() {
+        spawn_ui(child, UIParentCom::new(entity, order as i32), entities, ui, localised);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct StageEntity {
     transform: Option<TransformCom>,
     sprite: Option<SpriteCom>,
     text: Option<TextCom>,
+    localisation: Option<LocalisedTextCom>,
     body: Option<PersistentRigidBody>,
-    collider: Option<PersistentCollider>
+    colliders: Vec<PersistentCollider>
+}
+
+// Stage files start with the magic number and the format version, followed by the entities
+// Files without the magic number are from before the format was versioned and are converted when loaded
+const STAGE_MAGIC: &[u8; 4] = b"IVST";
+const STAGE_VERSION: u32 = 1;
+
+fn stage_into_file(elements: &[StageEntity], file: &str) -> Result<(), String> {
+    let mut data = STAGE_MAGIC.to_vec();
+    data.extend_from_slice(&STAGE_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut data, elements).map_err(|e| e.to_string())?;
+    std::fs::write(file, data).map_err(|e| e.to_string())
+}
+
+fn stage_from_file(file: &str) -> Result<Vec<StageEntity>, String> {
+    let data = std::fs::read(file).map_err(|e| e.to_string())?;
+    if data.len() >= 8 && data.starts_with(STAGE_MAGIC) {
+        match u32::from_le_bytes(data[4..8].try_into().unwrap()) {
+            STAGE_VERSION => bincode::deserialize(&data[8..]).map_err(|e| e.to_string()),
+            version => Err(format!("unsupported stage version {}", version))
+        }
+    } else {
+        let elements: Vec<StageEntityV0> = bincode::deserialize(&data).map_err(|e| e.to_string())?;
+        Ok(elements.into_iter().map(|e| e.into()).collect())
+    }
+}
+
+// The unversioned format, entities had at most one collider and bodies only stored their status
+#[derive(Deserialize)]
+struct StageEntityV0 {
+    transform: Option<TransformV0>,
+    sprite: Option<SpriteCom>,
+    text: Option<TextCom>,
+    body: Option<RigidBodyV0>,
+    collider: Option<ColliderV0>
+}
+
+#[derive(Deserialize)]
+struct TransformV0 {
+    pos: Vector
+}
+
+#[derive(Deserialize)]
+struct RigidBodyV0 {
+    #[serde(with = "BodyStatusDef")]
+    status: BodyStatus
+}
+
+#[derive(Deserialize)]
+struct ColliderV0 {
+    shape: ShapeDef,
+    position: IsometryDef
+}
+
+// Missing properties get the defaults that were used for all bodies and colliders at the time
+impl From<StageEntityV0> for StageEntity {
+    fn from(e: StageEntityV0) -> Self {
+        let material = BasicMaterial::<f32>::default();
+        Self {
+            transform: e.transform.map(|t| TransformCom::new(t.pos)),
+            sprite: e.sprite,
+            text: e.text,
+            localisation: None,
+            body: e.body.map(|b| PersistentRigidBody {
+                status: b.status,
+                mass: 1.0,
+                angular_inertia: 0.0,
+                linear_damping: 0.0,
+                angular_damping: 0.0,
+                gravity_scale: 1.0,
+                rotation_locked: false
+            }),
+            colliders: e.collider.into_iter().map(|c| PersistentCollider {
+                shape: c.shape,
+                position: c.position,
+                sensor: false,
+                groups: (&CollisionGroups::new()).into(),
+                density: 0.0,
+                friction: material.friction,
+                restitution: material.restitution
+            }).collect()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -164,46 +369,194 @@ impl SpriteSheet {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UILayout {
+    pub elements: Vec<UIElement>
+}
+
+impl UILayout {
+    pub fn new() -> Self {
+        Self {
+            elements: Vec::new()
+        }
+    }
+
+    pub fn add_element(&mut self, element: UIElement) {
+        self.elements.push(element);
+    }
+
+    // Layouts are stored as RON so that they can be written and edited by hand
+    pub fn into_file(&self, file: &str) {
+        let text = ron::ser::to_string_pretty(&self, ron::ser::PrettyConfig::new()).unwrap();
+        std::fs::write(file, text).unwrap();
+    }
+
+    pub fn from_file(file: &str) -> Option<Self> {
+        let text = std::fs::read_to_string(file).ok()?;
+        match ron::de::from_str(&text) {
+            Ok(layout) => Some(layout),
+            Err(e) => { eprintln!("could not parse UI layout \"{}\": {}", file, e); None }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UIElement {
+    pub constraint: ConstraintCom,
+    pub widget: UIWidget,
+    pub style: Option<StyleUICom>,
+    pub localisation: Option<LocalisedTextCom>,
+    pub children: Vec<UIElement>
+}
+
+impl UIElement {
+    pub fn new(constraint: ConstraintCom, widget: UIWidget) -> Self {
+        Self {
+            constraint: constraint,
+            widget: widget,
+            style: None,
+            localisation: None,
+            children: Vec::new()
+        }
+    }
+
+    pub fn with_localisation(mut self, localisation: LocalisedTextCom) -> Self {
+        self.localisation = Some(localisation);
+        self
+    }
+
+    pub fn with_style(mut self, style: StyleUICom) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn with_child(mut self, child: UIElement) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+// Only groups lay out their children, the children of any other widget are ignored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UIWidget {
+    Empty,
+    Group { sprite: String, vertical: bool, padding: UIEdges, spacing: u32 },
+    ScrollGroup { sprite: String, scrollbar: String, scrollbar_handle: String, vertical: bool, padding: UIEdges, spacing: u32 },
+    Text { text: String, font: String },
+    Button { sprite: String, sprite_hovered: String, sprite_pressed: String, sprite_disabled: String, element_name: String, disabled: bool },
+    TextField { background: String, text: String, font: String, element_name: String, max_length: Option<usize>, masked: bool },
+    Checkbox { sprite: String, sprite_checked: String, element_name: String, checked: bool },
+    Slider { track: String, handle: String, element_name: String, min: f32, max: f32, step: f32, value: f32 },
+    Dropdown { background: String, list_background: String, font: String, element_name: String, options: Vec<String>, selected: usize },
+    ProgressBar { background: String, fill: String, progress: f32 },
+    Image { sprite: String }
+}
+
 // Serialisable versions of engine objects from other libraries
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PersistentRigidBody {
     #[serde(with = "BodyStatusDef")]
-    status: BodyStatus
+    status: BodyStatus,
+    mass: f32,
+    angular_inertia: f32,
+    linear_damping: f32,
+    angular_damping: f32,
+    gravity_scale: f32,
+    rotation_locked: bool
 }
 
-impl From<&RigidBody<f32>> for PersistentRigidBody {
-    fn from(rb: &RigidBody<f32>) -> Self {
+impl PersistentRigidBody {
+    // Bodies without properties are saved with the defaults of a RigidBodyBuilder, their inertia includes their colliders
+    fn new(rb: &RigidBody<f32>, properties: Option<BodyProperties>) -> Self {
+        let properties = properties.unwrap_or_default();
         Self {
-            status: rb.status()
+            status: rb.status(),
+            mass: properties.mass,
+            angular_inertia: properties.angular_inertia,
+            linear_damping: rb.linear_damping(),
+            angular_damping: rb.angular_damping(),
+            gravity_scale: properties.gravity_scale,
+            rotation_locked: rb.kinematic_rotations()
         }
     }
 }
 
-impl Into<RigidBody<f32>> for PersistentRigidBody {
-    fn into(self) -> RigidBody<f32> {
-        RigidBodyDesc::new().status(self.status.into()).mass(1.0).build()
+impl Into<RigidBodyBuilder> for PersistentRigidBody {
+    fn into(self) -> RigidBodyBuilder {
+        RigidBodyBuilder::new()
+            .with_status(self.status)
+            .with_mass(self.mass)
+            .with_angular_inertia(self.angular_inertia)
+            .with_linear_damping(self.linear_damping)
+            .with_angular_damping(self.angular_damping)
+            .with_gravity_scale(self.gravity_scale)
+            .with_rotation_locked(self.rotation_locked)
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct PersistentCollider {
     shape: ShapeDef,
-    position: IsometryDef
+    position: IsometryDef,
+    sensor: bool,
+    groups: CollisionGroupsDef,
+    density: f32,
+    friction: f32,
+    restitution: f32
+}
+
+impl PersistentCollider {
+    // Colliders with shapes that can't be serialised are left out
+    fn new(c: &Collider<f32, DefaultBodyHandle>) -> Option<Self> {
+        let material = c.material().downcast_ref::<BasicMaterial<f32>>().cloned().unwrap_or_default();
+        Some(Self {
+            shape: ShapeDef::new(c.shape_handle())?,
+            position: c.position_wrt_body().into(),
+            sensor: c.is_sensor(),
+            groups: c.collision_groups().into(),
+            density: c.density(),
+            friction: material.friction,
+            restitution: material.restitution
+        })
+    }
+
+    fn into_collider(self, rb: &DefaultBodyHandle) -> Collider<f32, DefaultBodyHandle> {
+        ColliderDesc::new(self.shape.into())
+            .position(self.position.into())
+            .sensor(self.sensor)
+            .collision_groups(self.groups.into())
+            .density(self.density)
+            .material(MaterialHandle::new(BasicMaterial::new(self.restitution, self.friction)))
+            .build(BodyPartHandle(rb.clone(), 0))
+    }
+}
+
+// The groups as bit masks, ncollide only exposes them one group at a time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CollisionGroupsDef {
+    membership: u32,
+    whitelist: u32,
+    blacklist: u32
 }
 
-impl From<&Collider<f32, DefaultBodyHandle>> for PersistentCollider {
-    fn from(c: &Collider<f32, DefaultBodyHandle>) -> Self {
+impl From<&CollisionGroups> for CollisionGroupsDef {
+    fn from(g: &CollisionGroups) -> Self {
+        let mask = |f: &dyn Fn(usize) -> bool| (0..=CollisionGroups::max_group_id()).filter(|i| f(*i)).fold(0, |mask, i| mask | 1 << i);
         Self {
-            shape: c.shape_handle().into(),
-            position: c.position_wrt_body().into()
+            membership: mask(&|i| g.is_member_of(i)),
+            whitelist: mask(&|i| g.is_group_whitelisted(i)),
+            blacklist: mask(&|i| g.is_group_blacklisted(i))
         }
     }
 }
 
-impl PersistentCollider {
-    fn into_collider(self, rb: &DefaultBodyHandle) -> Collider<f32, DefaultBodyHandle> {
-        ColliderDesc::new(self.shape.into()).position(self.position.into()).build(BodyPartHandle(rb.clone(), 0))
+impl Into<CollisionGroups> for CollisionGroupsDef {
+    fn into(self) -> CollisionGroups {
+        CollisionGroups::new()
+            .with_membership_by_mask(self.membership)
+            .with_whitelist_by_mask(self.whitelist)
+            .with_blacklist_by_mask(self.blacklist)
     }
 }
 
@@ -219,18 +572,37 @@ enum BodyStatusDef {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 enum ShapeDef {
     Cuboid(CuboidDef),
-    ConvexPolygon(ConvexPolygonDef)
+    ConvexPolygon(ConvexPolygonDef),
+    Ball(BallDef),
+    Capsule(CapsuleDef),
+    Segment(SegmentDef),
+    Polyline(PolylineDef),
+    HeightField(HeightFieldDef),
+    Compound(CompoundDef)
 }
 
-impl From<&ShapeHandle<f32>> for ShapeDef {
-    fn from(s: &ShapeHandle<f32>) -> Self {
-        if s.is_shape::<Cuboid<f32>>() {
+impl ShapeDef {
+    // Custom shapes and compounds containing them have no serialisable version
+    fn new(s: &ShapeHandle<f32>) -> Option<Self> {
+        Some(if s.is_shape::<Cuboid<f32>>() {
             ShapeDef::Cuboid(s.as_shape::<Cuboid<f32>>().unwrap().into())
         } else if s.is_shape::<ConvexPolygon<f32>>() {
             ShapeDef::ConvexPolygon(s.as_shape::<ConvexPolygon<f32>>().unwrap().into())
+        } else if s.is_shape::<Ball<f32>>() {
+            ShapeDef::Ball(s.as_shape::<Ball<f32>>().unwrap().into())
+        } else if s.is_shape::<Capsule<f32>>() {
+            ShapeDef::Capsule(s.as_shape::<Capsule<f32>>().unwrap().into())
+        } else if s.is_shape::<Segment<f32>>() {
+            ShapeDef::Segment(s.as_shape::<Segment<f32>>().unwrap().into())
+        } else if s.is_shape::<Polyline<f32>>() {
+            ShapeDef::Polyline(s.as_shape::<Polyline<f32>>().unwrap().into())
+        } else if s.is_shape::<HeightField<f32>>() {
+            ShapeDef::HeightField(s.as_shape::<HeightField<f32>>().unwrap().into())
+        } else if s.is_shape::<Compound<f32>>() {
+            ShapeDef::Compound(CompoundDef::new(s.as_shape::<Compound<f32>>().unwrap())?)
         } else {
-            panic!("attempted to serialize unknown shape");
-        }
+            return None;
+        })
     }
 }
 
@@ -238,7 +610,13 @@ impl Into<ShapeHandle<f32>> for ShapeDef {
     fn into(self) -> ShapeHandle<f32> {
         match self {
             ShapeDef::Cuboid(s) => ShapeHandle::new::<Cuboid<f32>>(s.into()),
-            ShapeDef::ConvexPolygon(s) => ShapeHandle::new::<ConvexPolygon<f32>>(s.into())
+            ShapeDef::ConvexPolygon(s) => ShapeHandle::new::<ConvexPolygon<f32>>(s.into()),
+            ShapeDef::Ball(s) => ShapeHandle::new::<Ball<f32>>(s.into()),
+            ShapeDef::Capsule(s) => ShapeHandle::new::<Capsule<f32>>(s.into()),
+            ShapeDef::Segment(s) => ShapeHandle::new::<Segment<f32>>(s.into()),
+            ShapeDef::Polyline(s) => ShapeHandle::new::<Polyline<f32>>(s.into()),
+            ShapeDef::HeightField(s) => ShapeHandle::new::<HeightField<f32>>(s.into()),
+            ShapeDef::Compound(s) => ShapeHandle::new::<Compound<f32>>(s.into())
         }
     }
 }
@@ -281,6 +659,128 @@ impl Into<ConvexPolygon<f32>> for ConvexPolygonDef {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BallDef {
+    radius: f32
+}
+
+impl From<&Ball<f32>> for BallDef {
+    fn from(b: &Ball<f32>) -> Self {
+        Self {
+            radius: b.radius
+        }
+    }
+}
+
+impl Into<Ball<f32>> for BallDef {
+    fn into(self) -> Ball<f32> {
+        Ball::new(self.radius)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CapsuleDef {
+    half_height: f32,
+    radius: f32
+}
+
+impl From<&Capsule<f32>> for CapsuleDef {
+    fn from(c: &Capsule<f32>) -> Self {
+        Self {
+            half_height: c.half_height,
+            radius: c.radius
+        }
+    }
+}
+
+impl Into<Capsule<f32>> for CapsuleDef {
+    fn into(self) -> Capsule<f32> {
+        Capsule::new(self.half_height, self.radius)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegmentDef {
+    a: Vector,
+    b: Vector
+}
+
+impl From<&Segment<f32>> for SegmentDef {
+    fn from(s: &Segment<f32>) -> Self {
+        Self {
+            a: Vector::from(s.a.coords),
+            b: Vector::from(s.b.coords)
+        }
+    }
+}
+
+impl Into<Segment<f32>> for SegmentDef {
+    fn into(self) -> Segment<f32> {
+        Segment::new(Point::from(self.a.0), Point::from(self.b.0))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PolylineDef {
+    points: Vec<Vector>,
+    edges: Vec<(usize, usize)>
+}
+
+impl From<&Polyline<f32>> for PolylineDef {
+    fn from(p: &Polyline<f32>) -> Self {
+        Self {
+            points: p.points().iter().map(|p| Vector::from(p.coords)).collect(),
+            edges: p.edges().iter().map(|e| (e.indices.x, e.indices.y)).collect()
+        }
+    }
+}
+
+impl Into<Polyline<f32>> for PolylineDef {
+    fn into(self) -> Polyline<f32> {
+        Polyline::new(self.points.iter().map(|p| Point::from(p.0)).collect(), Some(self.edges.iter().map(|(a, b)| Point2::new(*a, *b)).collect()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HeightFieldDef {
+    heights: Vec<f32>,
+    scale: Vector
+}
+
+impl From<&HeightField<f32>> for HeightFieldDef {
+    fn from(h: &HeightField<f32>) -> Self {
+        Self {
+            heights: h.heights().iter().copied().collect(),
+            scale: Vector::from(*h.scale())
+        }
+    }
+}
+
+impl Into<HeightField<f32>> for HeightFieldDef {
+    fn into(self) -> HeightField<f32> {
+        HeightField::new(DVector::from_vec(self.heights), Vector2::new(self.scale.x, self.scale.y))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CompoundDef {
+    shapes: Vec<(IsometryDef, ShapeDef)>
+}
+
+impl CompoundDef {
+    fn new(c: &Compound<f32>) -> Option<Self> {
+        Some(Self {
+            shapes: c.shapes().iter().map(|(position, shape)| Some(((*position).into(), ShapeDef::new(shape)?))).collect::<Option<_>>()?
+        })
+    }
+}
+
+impl Into<Compound<f32>> for CompoundDef {
+    fn into(self) -> Compound<f32> {
+        Compound::new(self.shapes.into_iter().map(|(position, shape)| (position.into(), shape.into())).collect())
+    }
+}
+
 type Isometryf = nphysics2d::math::Isometry<f32>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -303,3 +803,80 @@ impl Into<Isometryf> for IsometryDef {
         Isometryf::from_parts((*self.translation).into(), UnitComplex::from_angle(self.rotation))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{PositionConstraint, SizeConstraint};
+
+    fn constraint() -> ConstraintCom {
+        ConstraintCom::new(PositionConstraint::Center, PositionConstraint::Start, SizeConstraint::Proportion(0.5), SizeConstraint::Pixels(40))
+    }
+
+    #[test]
+    fn layout_round_trips_through_ron() {
+        let mut layout = UILayout::new();
+        layout.add_element(UIElement::new(constraint(), UIWidget::Group { sprite: "panel".to_string(), vertical: true, padding: UIEdges::new(1, 2, 3, 4), spacing: 5 })
+            .with_style(StyleUICom::new("menu"))
+            .with_child(UIElement::new(constraint(), UIWidget::Text { text: "Play".to_string(), font: "normal".to_string() })
+                .with_localisation(LocalisedTextCom::new("menu.play", &["1"])))
+            .with_child(UIElement::new(constraint(), UIWidget::TextField { background: "field".to_string(), text: String::new(), font: "normal".to_string(), element_name: "name".to_string(), max_length: Some(12), masked: false })));
+
+        let text = ron::ser::to_string_pretty(&layout, ron::ser::PrettyConfig::new()).unwrap();
+        let parsed: UILayout = ron::de::from_str(&text).unwrap();
+        assert_eq!(ron::ser::to_string(&parsed).unwrap(), ron::ser::to_string(&layout).unwrap());
+        assert_eq!(parsed.elements[0].children.len(), 2);
+    }
+
+    #[test]
+    fn stage_asset_loads() {
+        let elements = stage_from_file("tests/assets/stage.mst").unwrap();
+        assert!(!elements.is_empty());
+    }
+
+    #[test]
+    fn unversioned_stage_is_converted() {
+        let elements = stage_from_file("tests/assets/stage_v0.mst").unwrap();
+        assert!(!elements.is_empty());
+        assert!(elements.iter().all(|e| e.colliders.len() <= 1));
+        assert!(elements.iter().filter_map(|e| e.body.as_ref()).all(|b| b.mass == 1.0 && b.gravity_scale == 1.0));
+    }
+
+    #[test]
+    fn stage_round_trips_and_rejects_unknown_versions() {
+        let path = std::env::temp_dir().join(format!("invader-stage-{}.mst", std::process::id()));
+        let path = path.to_str().unwrap();
+        let elements = stage_from_file("tests/assets/stage.mst").unwrap();
+        stage_into_file(&elements, path).unwrap();
+        assert_eq!(stage_from_file(path).unwrap().len(), elements.len());
+
+        let mut data = std::fs::read(path).unwrap();
+        data[4..8].copy_from_slice(&(STAGE_VERSION + 1).to_le_bytes());
+        std::fs::write(path, &data).unwrap();
+        assert!(stage_from_file(path).is_err());
+
+        std::fs::write(path, &data[..6]).unwrap();
+        assert!(stage_from_file(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unknown_shapes_are_not_saved() {
+        let plane = ShapeHandle::new(Plane::new(Vector2::y_axis()));
+        let ball = ShapeHandle::new(Ball::new(1.0));
+        assert!(ShapeDef::new(&plane).is_none());
+        assert!(ShapeDef::new(&ball).is_some());
+
+        let compound = |child: &ShapeHandle<f32>| ShapeHandle::new(Compound::new(vec![(Isometryf::identity(), ball.clone()), (Isometryf::identity(), child.clone())]));
+        assert!(ShapeDef::new(&compound(&plane)).is_none());
+        assert!(ShapeDef::new(&compound(&ball)).is_some());
+    }
+
+    #[test]
+    fn layout_from_file_rejects_malformed_files() {
+        let path = std::env::temp_dir().join("invader_malformed_layout.mui");
+        std::fs::write(&path, "(elements: [(constraint: oops").unwrap();
+        assert!(UILayout::from_file(path.to_str().unwrap()).is_none());
+        assert!(UILayout::from_file("does/not/exist.mui").is_none());
+    }
+}
@@ -43,20 +43,16 @@ impl<'a> System<'a> for NetworkSyncSys {
         Write<'a, PhysicsRes>,
         ReadStorage<'a, NetMasterTransformCom>,
         ReadStorage<'a, NetSlaveTransformCom>,
-        WriteStorage<'a, TransformCom>,
-        ReadStorage<'a, RigidBodyCom>,
-        ReadStorage<'a, ColliderCom>);
+        WriteStorage<'a, TransformCom>);
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, updater, mut net_events, mut net_requests, mut physics, master_transform_flags, slave_transform_flags, mut transforms, rigid_bodies, colliders) = data;
+        let (entities, updater, mut net_events, mut net_requests, mut physics, master_transform_flags, slave_transform_flags, mut transforms) = data;
 
         for request in net_requests.iter() {
             match request {
                 NetworkRequest::Open => {
                    self.imp.close();
-                    for (entity, _, rb, col) in (&entities, &slave_transform_flags, &rigid_bodies, &colliders).join() {
-                        physics.bodies.remove(rb.0);
-                        physics.colliders.remove(col.0);
+                    for (entity, _) in (&entities, &slave_transform_flags).join() {
                         updater.remove::<SpriteCom>(entity);
                         updater.remove::<TransformCom>(entity);
                         updater.remove::<NetSlaveTransformCom>(entity);
@@ -67,9 +63,7 @@ impl<'a> System<'a> for NetworkSyncSys {
                 },
                 NetworkRequest::Close => {
                     self.imp.close();
-                    for (entity, _, rb, col) in (&entities, &slave_transform_flags, &rigid_bodies, &colliders).join() {
-                        physics.bodies.remove(rb.0);
-                        physics.colliders.remove(col.0);
+                    for (entity, _) in (&entities, &slave_transform_flags).join() {
                         updater.remove::<SpriteCom>(entity);
                         updater.remove::<TransformCom>(entity);
                         updater.remove::<NetSlaveTransformCom>(entity);
@@ -95,7 +89,7 @@ impl<'a> System<'a> for NetworkSyncSys {
                 NetworkEvent::PeerMoved(origin_id, t) => {
                     for (slave_transform, transform) in (&slave_transform_flags, &mut transforms).join() {
                         if origin_id == &slave_transform.0 {
-                            transform.pos = t.pos;
+                            *transform = t.clone();
                         }
                     }
                 },
@@ -108,10 +102,8 @@ impl<'a> System<'a> for NetworkSyncSys {
                         .with(rb).with(col).build();
                 },
                 NetworkEvent::PeerDisconnected(origin_id) => {
-                    for (entity, slave_transform, rb, col) in (&entities, &slave_transform_flags, &rigid_bodies, &colliders).join() {
+                    for (entity, slave_transform) in (&entities, &slave_transform_flags).join() {
                         if origin_id == &slave_transform.0 {
-                            physics.bodies.remove(rb.0);
-                            physics.colliders.remove(col.0);
                             updater.remove::<SpriteCom>(entity);
                             updater.remove::<TransformCom>(entity);
                             updater.remove::<NetSlaveTransformCom>(entity);
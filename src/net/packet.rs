@@ -68,6 +68,9 @@ impl Packet {
                 packet.extend_from_slice(&p.origin_id.to_bytes());
                 packet.extend_from_slice(&p.transform.pos.x.to_le_bytes());
                 packet.extend_from_slice(&p.transform.pos.y.to_le_bytes());
+                packet.extend_from_slice(&p.transform.rotation.to_le_bytes());
+                packet.extend_from_slice(&p.transform.scale.x.to_le_bytes());
+                packet.extend_from_slice(&p.transform.scale.y.to_le_bytes());
             },
             _ => packet.push(0)
         }
@@ -75,33 +78,41 @@ impl Packet {
         packet
     }
 
+    // Packets that are too short for their type, like transforms from older versions, are treated as empty
     pub fn from_bytes(packet: Vec<u8>) -> Self {
-        match packet[0] {
+        match packet.first().copied().unwrap_or(0) {
             1 => Packet::ConRequest(ConRequestPacket::new()),
-            2 => Packet::ConAcknowledge(ConAcknowledgePacket {
+            2 if packet.len() >= 9 => Packet::ConAcknowledge(ConAcknowledgePacket {
                 origin_id: NetID::from_bytes(&packet[1..=4]),
                 assigned_id: NetID::from_bytes(&packet[5..=8])
             }),
-            3 => Packet::ConNew(ConNewPacket {
+            3 if packet.len() >= 11 => Packet::ConNew(ConNewPacket {
                 peer_id: NetID::from_bytes(&packet[1..=4]),
                 socket: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(packet[5], packet[6], packet[7], packet[8])), u16::from_le_bytes(packet[9..=10].try_into().unwrap()))
             }),
-            4 => Packet::ConDelete(ConDeletePacket::new(
+            4 if packet.len() >= 5 => Packet::ConDelete(ConDeletePacket::new(
                 NetID::from_bytes(&packet[1..=4])
             )),
-            5 => Packet::ConRedirect(ConRedirectPacket::new(
+            5 if packet.len() >= 7 => Packet::ConRedirect(ConRedirectPacket::new(
                 SocketAddr::new(IpAddr::V4(Ipv4Addr::new(packet[1], packet[2], packet[3], packet[4])), u16::from_le_bytes(packet[5..=6].try_into().unwrap()))
             )),
-            6 => Packet::ConHeartbeat(ConHeartbeatPacket::new(
+            6 if packet.len() >= 5 => Packet::ConHeartbeat(ConHeartbeatPacket::new(
                 NetID::from_bytes(&packet[1..=4])
             )),
-            7 => Packet::Transform(TransformPacket {
+            7 if packet.len() >= 25 => Packet::Transform(TransformPacket {
                 origin_id: NetID::from_bytes(&packet[1..=4]),
                 transform: TransformCom::new(
                     Vector::new(
                         f32::from_le_bytes(packet[5..=8].try_into().unwrap()),
                         f32::from_le_bytes(packet[9..=12].try_into().unwrap())
                     )
+                ).with_rotation(
+                    f32::from_le_bytes(packet[13..=16].try_into().unwrap())
+                ).with_scale(
+                    Vector::new(
+                        f32::from_le_bytes(packet[17..=20].try_into().unwrap()),
+                        f32::from_le_bytes(packet[21..=24].try_into().unwrap())
+                    )
                 )
             }),
             _ => Packet::Empty
@@ -192,3 +203,33 @@ pub struct TransformPacket {
     pub origin_id: NetID,
     pub transform: TransformCom
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform_packet() -> Packet {
+        Packet::Transform(TransformPacket::new(NetID::new().init(7), TransformCom::new(Vector::new(1.0, -2.0)).with_rotation(0.5).with_scale(Vector::new(3.0, 4.0))))
+    }
+
+    #[test]
+    fn transform_round_trips() {
+        match Packet::from_bytes(transform_packet().into_bytes()) {
+            Packet::Transform(p) => {
+                assert!(p.origin_id == NetID::new().init(7));
+                assert_eq!((p.transform.pos.x, p.transform.pos.y), (1.0, -2.0));
+                assert_eq!(p.transform.rotation, 0.5);
+                assert_eq!((p.transform.scale.x, p.transform.scale.y), (3.0, 4.0));
+            },
+            p => panic!("expected a transform packet, got {:?}", p)
+        }
+    }
+
+    #[test]
+    fn short_packets_are_empty() {
+        let bytes = transform_packet().into_bytes();
+        assert!(matches!(Packet::from_bytes(bytes[..13].to_vec()), Packet::Empty));
+        assert!(matches!(Packet::from_bytes(vec![2, 0, 0]), Packet::Empty));
+        assert!(matches!(Packet::from_bytes(Vec::new()), Packet::Empty));
+    }
+}
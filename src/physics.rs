@@ -1,60 +1,161 @@
+use std::collections::{HashMap, HashSet};
+
 use serde::{Serialize, Deserialize};
 
 use specs::*;
 use specs::storage::ComponentEvent;
+use specs::world::Index;
 
-use nalgebra::{Isometry2, Vector2, Point};
+use nalgebra::{Isometry2, Vector2, DVector, Point};
 use nphysics2d::object::*;
 use nphysics2d::force_generator::DefaultForceGeneratorSet;
 use nphysics2d::joint::DefaultJointConstraintSet;
 use nphysics2d::world::{DefaultMechanicalWorld, DefaultGeometricalWorld};
-use ncollide2d::shape::{ShapeHandle, Shape, Cuboid, ConvexPolygon};
+use nphysics2d::material::{MaterialHandle, BasicMaterial};
+use ncollide2d::shape::{ShapeHandle, Shape, Cuboid, ConvexPolygon, Ball, Capsule, Segment, Polyline, HeightField, Compound};
+use ncollide2d::pipeline::narrow_phase::ContactEvent;
+use ncollide2d::query::{Proximity, Ray, DefaultTOIDispatcher};
+use ncollide2d::bounding_volume::{AABB, BoundingVolume};
 
 pub use nphysics2d::math::{Force, ForceType};
-pub use nphysics2d::object::Body;
-
-use invader_macro::DefaultConstructor;
+pub use nphysics2d::object::{Body, BodyStatus};
+pub use ncollide2d::pipeline::object::CollisionGroups;
 
 use crate::misc::Vector;
 
-#[derive(Component, Debug)]
-#[storage(VecStorage)]
+event_queue! {
+    CollisionEventQueue: pub enum CollisionEvent {
+        ContactStarted(Entity, Entity),
+        ContactStopped(Entity, Entity),
+        // The sensor comes first, followed by the entity that entered or left it
+        SensorEntered(Entity, Entity),
+        SensorExited(Entity, Entity)
+    }
+}
+
+// The body is removed from the PhysicsRes along with the component
+#[derive(Debug)]
 pub struct RigidBodyCom(pub DefaultBodyHandle);
 
-#[derive(Component, Debug)]
-#[storage(VecStorage)]
-pub struct ColliderCom(pub DefaultColliderHandle);
+impl Component for RigidBodyCom {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
 
-#[derive(Debug, Clone, DefaultConstructor, Serialize, Deserialize)]
+// All colliders of an entity are attached to its body and removed from the PhysicsRes along with the component
+#[derive(Debug)]
+pub struct ColliderCom(pub Vec<DefaultColliderHandle>);
+
+impl Component for ColliderCom {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+// Rotation is counterclockwise in radians around pos, scale only affects rendering
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransformCom {
     pub pos: Vector,
+    pub rotation: f32,
+    pub scale: Vector
 }
 
-impl Component for TransformCom {
-    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
-}
+impl TransformCom {
+    pub fn new(pos: Vector) -> Self {
+        Self {
+            pos: pos,
+            rotation: 0.0,
+            scale: Vector::new(1.0, 1.0)
+        }
+    }
 
-impl Default for RigidBodyCom {
-    fn default() -> Self {
-        Self(DefaultBodyHandle::from_raw_parts(0, 0))
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector) -> Self {
+        self.scale = scale;
+        self
     }
 }
 
+impl Component for TransformCom {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
 pub struct PhysicsSys {
     external_transforms: BitSet,
     transform_event_reader: Option<ReaderId<ComponentEvent>>,
+    rigid_body_event_reader: Option<ReaderId<ComponentEvent>>,
+    collider_event_reader: Option<ReaderId<ComponentEvent>>,
+    // The handles of each entity's components, which are already gone by the time they are removed
+    rigid_body_handles: HashMap<Index, DefaultBodyHandle>,
+    collider_handles: HashMap<Index, Vec<DefaultColliderHandle>>,
+    // Colliders removed this frame still need their entities for the events caused by their removal
+    removed_colliders: Vec<DefaultColliderHandle>,
+    // Handles made before the last frame, which are freed if no component has claimed them by now
+    unclaimed_bodies: Vec<DefaultBodyHandle>,
+    unclaimed_colliders: Vec<DefaultColliderHandle>
 }
 
 impl<'a> System<'a> for PhysicsSys {
-    type SystemData = (Write<'a, PhysicsRes>,
+    type SystemData = (Entities<'a>,
+        Write<'a, PhysicsRes>,
+        Write<'a, CollisionEventQueue>,
         WriteStorage<'a, TransformCom>,
         ReadStorage<'a, RigidBodyCom>,
         ReadStorage<'a, ColliderCom>);
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut physics, mut transforms, rigid_bodies, _colliders) = data;
+        let (entities, mut physics, mut collision_events, mut transforms, rigid_bodies, colliders) = data;
         let delta_time = physics.delta_time;
 
+        // Free the handles of components that were removed or replaced, colliders first so none are left without a body
+        let mut removal_events = Vec::new();
+        for event in colliders.channel().read(self.collider_event_reader.as_mut().unwrap()) {
+            let (id, handles) = match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => (id, colliders.get(entities.entity(*id)).map_or(Vec::new(), |c| c.0.clone())),
+                ComponentEvent::Removed(id) => (id, Vec::new())
+            };
+            for handle in handles.iter() {
+                physics.collider_entities.insert(*handle, entities.entity(*id));
+            }
+            let old_handles = match handles.is_empty() {
+                true => self.collider_handles.remove(id),
+                false => self.collider_handles.insert(*id, handles.clone())
+            };
+            for handle in old_handles.unwrap_or_default().into_iter().filter(|h| !handles.contains(h)) {
+                removal_events.extend(physics.removal_events(handle));
+                physics.colliders.remove(handle);
+                self.removed_colliders.push(handle);
+            }
+        }
+        for event in rigid_bodies.channel().read(self.rigid_body_event_reader.as_mut().unwrap()) {
+            let (id, handle) = match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => (id, rigid_bodies.get(entities.entity(*id)).map(|rb| rb.0)),
+                ComponentEvent::Removed(id) => (id, None)
+            };
+            let old_handle = match handle {
+                Some(handle) => self.rigid_body_handles.insert(*id, handle),
+                None => self.rigid_body_handles.remove(id)
+            };
+            if let Some(old_handle) = old_handle.filter(|h| Some(*h) != handle) {
+                physics.bodies.remove(old_handle);
+                physics.body_properties.remove(&old_handle);
+            }
+        }
+
+        // Handles whose component never showed up, like ones inserted and removed in the same frame
+        let claimed: HashSet<DefaultColliderHandle> = self.collider_handles.values().flatten().copied().collect();
+        for handle in self.unclaimed_colliders.drain(..).filter(|h| !claimed.contains(h)) {
+            physics.colliders.remove(handle);
+        }
+        let claimed: HashSet<DefaultBodyHandle> = self.rigid_body_handles.values().copied().collect();
+        for handle in self.unclaimed_bodies.drain(..).filter(|h| !claimed.contains(h)) {
+            physics.bodies.remove(handle);
+            physics.body_properties.remove(&handle);
+        }
+        self.unclaimed_colliders = std::mem::take(&mut physics.created_colliders);
+        self.unclaimed_bodies = std::mem::take(&mut physics.created_bodies);
+
         self.external_transforms.clear();
         let transform_events = transforms.channel().read(self.transform_event_reader.as_mut().unwrap());
         for event in transform_events {
@@ -66,11 +167,22 @@ impl<'a> System<'a> for PhysicsSys {
         }
         for (transform, rigid_body, _) in (&transforms, &rigid_bodies, &self.external_transforms).join() {
             match physics.write_rigid_body(rigid_body) {
-                Some(rb) => rb.set_position(Isometry2::new(*transform.pos, 0.0)),
+                Some(rb) => rb.set_position(Isometry2::new(*transform.pos, transform.rotation)),
                 None => ()
             }
         }
 
+        // Bodies with a gravity scale have nphysics' gravity disabled and get it applied here instead
+        let gravity = physics.m_world.gravity;
+        for rigid_body in (&rigid_bodies).join() {
+            let gravity_scale = physics.body_properties(rigid_body).map_or(1.0, |p| p.gravity_scale);
+            if let Some(rb) = physics.write_rigid_body(rigid_body) {
+                if !rb.gravity_enabled() && gravity_scale != 0.0 {
+                    rb.apply_force(0, &Force::linear(gravity * gravity_scale), ForceType::AccelerationChange, false);
+                }
+            }
+        }
+
         physics.m_world.set_timestep(delta_time);
 
         // All components are part of the same struct, so this should be safe
@@ -86,15 +198,58 @@ impl<'a> System<'a> for PhysicsSys {
 
         for (transform, rigid_body) in (&mut transforms, &rigid_bodies).join() {
             match physics.read_rigid_body(rigid_body) {
-                Some(rb) => transform.pos = Vector(rb.position().translation.vector),
+                Some(rb) => {
+                    transform.pos = Vector(rb.position().translation.vector);
+                    transform.rotation = rb.position().rotation.angle();
+                },
                 None => ()
             }
         }
+
+        collision_events.clear();
+        for event in removal_events {
+            collision_events.push(event);
+        }
+        for event in physics.g_world.contact_events().iter() {
+            let (collider1, collider2) = match event {
+                ContactEvent::Started(c1, c2) | ContactEvent::Stopped(c1, c2) => (c1, c2)
+            };
+            if let (Some(entity1), Some(entity2)) = (physics.collider_entity(*collider1), physics.collider_entity(*collider2)) {
+                collision_events.push(match event {
+                    ContactEvent::Started(_, _) => CollisionEvent::ContactStarted(entity1, entity2),
+                    ContactEvent::Stopped(_, _) => CollisionEvent::ContactStopped(entity1, entity2)
+                });
+            }
+        }
+        for event in physics.g_world.proximity_events().iter() {
+            let sensor_first = physics.colliders.get(event.collider1).map_or(false, |c| c.is_sensor());
+            let (sensor, other) = match sensor_first {
+                true => (event.collider1, event.collider2),
+                false => (event.collider2, event.collider1)
+            };
+            if let (Some(sensor), Some(other)) = (physics.collider_entity(sensor), physics.collider_entity(other)) {
+                match (event.prev_status, event.new_status) {
+                    (Proximity::Intersecting, Proximity::Intersecting) => (),
+                    (_, Proximity::Intersecting) => collision_events.push(CollisionEvent::SensorEntered(sensor, other)),
+                    (Proximity::Intersecting, _) => collision_events.push(CollisionEvent::SensorExited(sensor, other)),
+                    _ => ()
+                }
+            }
+        }
+
+        for handle in self.removed_colliders.drain(..) {
+            physics.collider_entities.remove(&handle);
+        }
+
+        // The changes made by the simulation itself don't need to be written back into it next frame
+        transforms.channel().read(self.transform_event_reader.as_mut().unwrap()).for_each(drop);
     }
 
     fn setup(&mut self, world: &mut World) {
         Self::SystemData::setup(world);
         self.transform_event_reader = Some(world.write_component::<TransformCom>().register_reader());
+        self.rigid_body_event_reader = Some(world.write_component::<RigidBodyCom>().register_reader());
+        self.collider_event_reader = Some(world.write_component::<ColliderCom>().register_reader());
     }
 }
 
@@ -102,7 +257,14 @@ impl PhysicsSys {
     pub fn new() -> Self {
         Self {
             external_transforms: BitSet::new(),
-            transform_event_reader: None
+            transform_event_reader: None,
+            rigid_body_event_reader: None,
+            collider_event_reader: None,
+            rigid_body_handles: HashMap::new(),
+            collider_handles: HashMap::new(),
+            removed_colliders: Vec::new(),
+            unclaimed_bodies: Vec::new(),
+            unclaimed_colliders: Vec::new()
         }
     }
 }
@@ -114,7 +276,13 @@ pub struct PhysicsRes {
     pub bodies: DefaultBodySet<f32>,
     pub colliders: DefaultColliderSet<f32>,
     pub constraints: DefaultJointConstraintSet<f32>,
-    pub forces: DefaultForceGeneratorSet<f32>
+    pub forces: DefaultForceGeneratorSet<f32>,
+    // Kept up to date by the PhysicsSys as ColliderComs are inserted, changed and removed
+    collider_entities: HashMap<DefaultColliderHandle, Entity>,
+    body_properties: HashMap<DefaultBodyHandle, BodyProperties>,
+    // Handles made since the PhysicsSys last ran, their components have until the end of the next frame to be inserted
+    created_bodies: Vec<DefaultBodyHandle>,
+    created_colliders: Vec<DefaultColliderHandle>
 }
 
 impl PhysicsRes {
@@ -132,39 +300,95 @@ impl Default for PhysicsRes {
             bodies: DefaultBodySet::new(),
             colliders: DefaultColliderSet::new(),
             constraints: DefaultJointConstraintSet::new(),
-            forces: DefaultForceGeneratorSet::new()
+            forces: DefaultForceGeneratorSet::new(),
+            collider_entities: HashMap::new(),
+            body_properties: HashMap::new(),
+            created_bodies: Vec::new(),
+            created_colliders: Vec::new()
         }
     }
 }
 
 #[allow(dead_code)]
 impl PhysicsRes {
+    // The body has no colliders yet, so its inertia is only its own
     pub fn register_rigid_body(&mut self, rb: RigidBody<f32>) -> RigidBodyCom {
-        RigidBodyCom(self.bodies.insert(rb))
+        let properties = BodyProperties {
+            mass: rb.local_inertia().linear,
+            angular_inertia: rb.local_inertia().angular,
+            gravity_scale: if rb.gravity_enabled() { 1.0 } else { 0.0 }
+        };
+        let handle = self.bodies.insert(rb);
+        self.body_properties.insert(handle, properties);
+        self.created_bodies.push(handle);
+        RigidBodyCom(handle)
     }
 
     pub fn register_collider(&mut self, c: Collider<f32, DefaultBodyHandle>) -> ColliderCom {
-        ColliderCom(self.colliders.insert(c))
+        let handle = self.colliders.insert(c);
+        self.created_colliders.push(handle);
+        ColliderCom(vec![handle])
     }
 
     pub fn create_rigid_body(&mut self) -> RigidBodyCom {
-        RigidBodyCom(self.bodies.insert(RigidBodyDesc::new().mass(1.0).build()))
+        self.build_rigid_body(RigidBodyBuilder::new())
     }
 
     pub fn create_rigid_body_static(&mut self) -> RigidBodyCom {
-        RigidBodyCom(self.bodies.insert(RigidBodyDesc::new().status(BodyStatus::Static).build()))
+        self.build_rigid_body(RigidBodyBuilder::new().with_status(BodyStatus::Static).with_mass(0.0))
+    }
+
+    pub fn build_rigid_body(&mut self, builder: RigidBodyBuilder) -> RigidBodyCom {
+        let properties = builder.properties;
+        let handle = self.bodies.insert(builder.build());
+        self.body_properties.insert(handle, properties);
+        self.created_bodies.push(handle);
+        RigidBodyCom(handle)
     }
 
     pub fn create_collider(&mut self, shape: impl Shape<f32>, rb: &RigidBodyCom) -> ColliderCom {
-        ColliderCom(self.colliders.insert(ColliderDesc::new(ShapeHandle::new(shape)).build(BodyPartHandle(rb.0, 0))))
+        self.build_collider(ColliderBuilder::new(shape), rb)
     }
 
     pub fn create_collider_rectangle(&mut self, dim: Vector, offset: Vector, rb: &RigidBodyCom) -> ColliderCom {
-        ColliderCom(self.colliders.insert(ColliderDesc::new(ShapeHandle::new(Cuboid::new(*dim / 2.0))).position(Isometry2::new(*dim / 2.0 + *offset, 0.0)).build(BodyPartHandle(rb.0, 0))))
+        self.build_collider(ColliderBuilder::rectangle(dim, offset), rb)
     }
 
     pub fn create_collider_triangle(&mut self, point0: Vector, point1: Vector, point2: Vector, rb: &RigidBodyCom) -> ColliderCom {
-        ColliderCom(self.colliders.insert(ColliderDesc::new(ShapeHandle::new(ConvexPolygon::try_new(vec![Point::from(*point0), Point::from(*point1), Point::from(*point2)]).unwrap())).position(Isometry2::new(Vector2::new(0.0, 0.0), 0.0)).build(BodyPartHandle(rb.0, 0))))
+        self.build_collider(ColliderBuilder::triangle(point0, point1, point2), rb)
+    }
+
+    pub fn create_collider_ball(&mut self, radius: f32, offset: Vector, rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::ball(radius, offset), rb)
+    }
+
+    pub fn create_collider_capsule(&mut self, height: f32, radius: f32, offset: Vector, rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::capsule(height, radius, offset), rb)
+    }
+
+    pub fn create_collider_segment(&mut self, point0: Vector, point1: Vector, rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::segment(point0, point1), rb)
+    }
+
+    pub fn create_collider_polyline(&mut self, points: &[Vector], rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::polyline(points), rb)
+    }
+
+    pub fn create_collider_height_field(&mut self, heights: &[f32], width: f32, offset: Vector, rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::height_field(heights, width, offset), rb)
+    }
+
+    pub fn create_collider_compound(&mut self, shapes: Vec<(Vector, f32, ShapeHandle<f32>)>, rb: &RigidBodyCom) -> ColliderCom {
+        self.build_collider(ColliderBuilder::compound(shapes), rb)
+    }
+
+    pub fn build_collider(&mut self, builder: ColliderBuilder, rb: &RigidBodyCom) -> ColliderCom {
+        self.register_collider(builder.build(&rb.0))
+    }
+
+    // Attaches another collider to the same body as the existing ones
+    pub fn add_collider(&mut self, builder: ColliderBuilder, rb: &RigidBodyCom, c: &mut ColliderCom) {
+        c.0.extend(self.build_collider(builder, rb).0);
     }
 
     pub fn write_rigid_body(&mut self, rb: &RigidBodyCom) -> Option<&mut RigidBody<f32>> {
@@ -175,7 +399,478 @@ impl PhysicsRes {
         self.bodies.rigid_body(rb.0)
     }
 
-    pub fn read_collider(&self, c: &ColliderCom) -> Option<&Collider<f32, DefaultBodyHandle>> {
-        self.colliders.get(c.0)
+    // Only bodies added to the body set directly have no properties
+    pub fn body_properties(&self, rb: &RigidBodyCom) -> Option<BodyProperties> {
+        self.body_properties.get(&rb.0).copied()
+    }
+
+    // The mass and inertia exclude the colliders, whose share is kept when they are changed
+    pub fn set_mass(&mut self, rb: &RigidBodyCom, mass: f32) {
+        if let (Some(properties), Some(body)) = (self.body_properties.get_mut(&rb.0), self.bodies.rigid_body_mut(rb.0)) {
+            body.set_mass(body.local_inertia().linear - properties.mass + mass);
+            properties.mass = mass;
+        }
+    }
+
+    pub fn set_angular_inertia(&mut self, rb: &RigidBodyCom, angular_inertia: f32) {
+        if let (Some(properties), Some(body)) = (self.body_properties.get_mut(&rb.0), self.bodies.rigid_body_mut(rb.0)) {
+            body.set_angular_inertia(body.local_inertia().angular - properties.angular_inertia + angular_inertia);
+            properties.angular_inertia = angular_inertia;
+        }
+    }
+
+    pub fn set_gravity_scale(&mut self, rb: &RigidBodyCom, gravity_scale: f32) {
+        if let (Some(properties), Some(body)) = (self.body_properties.get_mut(&rb.0), self.bodies.rigid_body_mut(rb.0)) {
+            body.enable_gravity(gravity_scale == 1.0);
+            properties.gravity_scale = gravity_scale;
+        }
+    }
+
+    pub fn read_colliders<'a>(&'a self, c: &'a ColliderCom) -> impl Iterator<Item = &'a Collider<f32, DefaultBodyHandle>> {
+        c.0.iter().filter_map(move |handle| self.colliders.get(*handle))
+    }
+
+    pub fn collider_entity(&self, handle: DefaultColliderHandle) -> Option<Entity> {
+        self.collider_entities.get(&handle).copied()
+    }
+
+    // The closest entity along the ray within the distance, colliders that contain the origin are hit immediately
+    pub fn cast_ray(&self, origin: Vector, dir: Vector, max_dist: f32, groups: &CollisionGroups) -> Option<QueryHit> {
+        self.cast_ray_all(origin, dir, max_dist, groups).into_iter().next()
+    }
+
+    // All entities along the ray within the distance, ordered by distance
+    // Entities are only hit once, at their closest collider, and a direction without a length hits nothing
+    pub fn cast_ray_all(&self, origin: Vector, dir: Vector, max_dist: f32, groups: &CollisionGroups) -> Vec<QueryHit> {
+        if dir.norm() == 0.0 {
+            return Vec::new();
+        }
+        let ray = Ray::new(Point::from(*origin), dir.normalize());
+        let mut hits: Vec<QueryHit> = self.g_world.interferences_with_ray(&self.colliders, &ray, max_dist, groups)
+            .filter_map(|(handle, _, inter)| self.collider_entity(handle).map(|entity| QueryHit {
+                entity: entity,
+                distance: inter.toi,
+                point: Vector(ray.point_at(inter.toi).coords),
+                normal: Vector(inter.normal)
+            }))
+            .collect();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        let mut entities: Vec<Entity> = Vec::new();
+        hits.retain(|hit| {
+            let first = !entities.contains(&hit.entity);
+            entities.push(hit.entity);
+            first
+        });
+        hits
+    }
+
+    // The closest entity the shape would hit when moved along the direction, the point and normal are on the entity's collider
+    pub fn cast_shape(&self, shape: &dyn Shape<f32>, pos: Vector, rotation: f32, dir: Vector, max_dist: f32, groups: &CollisionGroups) -> Option<QueryHit> {
+        if dir.norm() == 0.0 {
+            return None;
+        }
+        let dir = dir.normalize();
+        let start = Isometry2::new(*pos, rotation);
+        let end = Isometry2::new(*pos + dir * max_dist, rotation);
+        let swept = shape.aabb(&start).merged(&shape.aabb(&end));
+        self.g_world.interferences_with_aabb(&self.colliders, &swept, groups)
+            .filter_map(|(handle, c)| {
+                let entity = self.collider_entity(handle)?;
+                let toi = ncollide2d::query::time_of_impact(&DefaultTOIDispatcher, &start, &dir, shape, c.position(), &Vector2::zeros(), c.shape(), max_dist, 0.0).ok()??;
+                Some(QueryHit {
+                    entity: entity,
+                    distance: toi.toi,
+                    point: Vector((c.position() * toi.witness2).coords),
+                    normal: Vector(c.position() * toi.normal2.into_inner())
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    // The entities with a collider containing the point
+    pub fn query_point(&self, point: Vector, groups: &CollisionGroups) -> Vec<Entity> {
+        let point = Point::from(*point);
+        self.query_entities(self.g_world.interferences_with_point(&self.colliders, &point, groups).map(|(handle, _)| handle))
+    }
+
+    // The entities with a collider overlapping the axis aligned box between the corners
+    pub fn query_aabb(&self, min: Vector, max: Vector, groups: &CollisionGroups) -> Vec<Entity> {
+        let aabb = AABB::new(Point::from(*min), Point::from(*max));
+        self.query_entities(self.g_world.interferences_with_aabb(&self.colliders, &aabb, groups).map(|(handle, _)| handle))
+    }
+
+    // nphysics drops the contacts and proximities of removed colliders without any events, so they are ended here instead
+    fn removal_events(&self, handle: DefaultColliderHandle) -> Vec<CollisionEvent> {
+        let mut events = Vec::new();
+        // Colliders that haven't been through a step yet aren't in any contacts
+        if self.colliders.get(handle).and_then(|c| c.graph_index()).is_none() {
+            return events;
+        }
+        for (collider1, _, collider2, _, _, _) in self.g_world.contacts_with(&self.colliders, handle, true).into_iter().flatten() {
+            if let (Some(entity1), Some(entity2)) = (self.collider_entity(collider1), self.collider_entity(collider2)) {
+                events.push(CollisionEvent::ContactStopped(entity1, entity2));
+            }
+        }
+        for (collider1, c1, collider2, _, _, proximity) in self.g_world.proximities_with(&self.colliders, handle, false).into_iter().flatten() {
+            let (sensor, other) = match c1.is_sensor() {
+                true => (collider1, collider2),
+                false => (collider2, collider1)
+            };
+            if let (Proximity::Intersecting, Some(sensor), Some(other)) = (proximity, self.collider_entity(sensor), self.collider_entity(other)) {
+                events.push(CollisionEvent::SensorExited(sensor, other));
+            }
+        }
+        events
+    }
+
+    fn query_entities(&self, handles: impl Iterator<Item = DefaultColliderHandle>) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = Vec::new();
+        for entity in handles.filter_map(|handle| self.collider_entity(handle)) {
+            if !entities.contains(&entity) {
+                entities.push(entity);
+            }
+        }
+        entities
+    }
+}
+
+// The distance is measured along the cast from its origin, the normal points away from the entity's collider
+#[derive(Debug, Clone)]
+pub struct QueryHit {
+    pub entity: Entity,
+    pub distance: f32,
+    pub point: Vector,
+    pub normal: Vector
+}
+
+// Collision groups are numbered from 0 to 29, colliders are members of all groups and interact with all groups by default
+pub struct ColliderBuilder {
+    desc: ColliderDesc<f32>,
+    material: BasicMaterial<f32>
+}
+
+#[allow(dead_code)]
+impl ColliderBuilder {
+    pub fn new(shape: impl Shape<f32>) -> Self {
+        Self {
+            desc: ColliderDesc::new(ShapeHandle::new(shape)),
+            material: BasicMaterial::default()
+        }
+    }
+
+    // The rectangle extends from the body's position plus the offset
+    pub fn rectangle(dim: Vector, offset: Vector) -> Self {
+        Self::new(Cuboid::new(*dim / 2.0)).with_position(Vector(*dim / 2.0 + *offset), 0.0)
+    }
+
+    pub fn triangle(point0: Vector, point1: Vector, point2: Vector) -> Self {
+        Self::new(ConvexPolygon::try_new(vec![Point::from(*point0), Point::from(*point1), Point::from(*point2)]).unwrap())
+    }
+
+    // The ball is centered on the body's position plus the offset
+    pub fn ball(radius: f32, offset: Vector) -> Self {
+        Self::new(Ball::new(radius)).with_position(offset, 0.0)
+    }
+
+    // The capsule is upright and centered on the body's position plus the offset, its height doesn't include the caps
+    pub fn capsule(height: f32, radius: f32, offset: Vector) -> Self {
+        Self::new(Capsule::new(height / 2.0, radius)).with_position(offset, 0.0)
+    }
+
+    pub fn segment(point0: Vector, point1: Vector) -> Self {
+        Self::new(Segment::new(Point::from(*point0), Point::from(*point1)))
+    }
+
+    // The points are connected in order, useful for the edges of terrain
+    pub fn polyline(points: &[Vector]) -> Self {
+        Self::new(Polyline::new(points.iter().map(|p| Point::from(**p)).collect(), None))
+    }
+
+    // The heights are evenly spaced over the width, starting at the body's position plus the offset
+    pub fn height_field(heights: &[f32], width: f32, offset: Vector) -> Self {
+        let shape = HeightField::new(DVector::from_row_slice(heights), Vector2::new(width, 1.0));
+        Self::new(shape).with_position(Vector::new(offset.x + width / 2.0, offset.y), 0.0)
+    }
+
+    // Each shape is placed at its offset and rotation relative to the collider, they should all be convex
+    pub fn compound(shapes: Vec<(Vector, f32, ShapeHandle<f32>)>) -> Self {
+        Self::new(Compound::new(shapes.into_iter().map(|(offset, rotation, shape)| (Isometry2::new(*offset, rotation), shape)).collect()))
+    }
+
+    pub fn with_position(mut self, offset: Vector, rotation: f32) -> Self {
+        self.desc.set_position(Isometry2::new(*offset, rotation));
+        self
+    }
+
+    // Sensors don't collide with anything, they only report entities entering and leaving them
+    pub fn with_sensor(mut self, sensor: bool) -> Self {
+        self.desc.set_is_sensor(sensor);
+        self
+    }
+
+    pub fn with_membership(mut self, groups: &[usize]) -> Self {
+        let mut collision_groups = self.desc.get_collision_groups();
+        collision_groups.set_membership(groups);
+        self.desc.set_collision_groups(collision_groups);
+        self
+    }
+
+    // Only the groups on the whitelist and not on the blacklist are collided with
+    pub fn with_whitelist(mut self, groups: &[usize]) -> Self {
+        let mut collision_groups = self.desc.get_collision_groups();
+        collision_groups.set_whitelist(groups);
+        self.desc.set_collision_groups(collision_groups);
+        self
+    }
+
+    pub fn with_blacklist(mut self, groups: &[usize]) -> Self {
+        let mut collision_groups = self.desc.get_collision_groups();
+        collision_groups.set_blacklist(groups);
+        self.desc.set_collision_groups(collision_groups);
+        self
+    }
+
+    // Colliders with a density add their mass to the body, by default they have none
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.desc.set_density(density);
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.material.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.material.restitution = restitution;
+        self
+    }
+
+    pub fn build(mut self, rb: &DefaultBodyHandle) -> Collider<f32, DefaultBodyHandle> {
+        self.desc.set_material(MaterialHandle::new(self.material));
+        self.desc.build(BodyPartHandle(*rb, 0))
+    }
+}
+
+// Properties of a body that nphysics doesn't keep track of, or that colliders change, kept by the PhysicsRes for each body
+#[derive(Copy, Clone, Debug)]
+pub struct BodyProperties {
+    pub mass: f32,
+    pub angular_inertia: f32,
+    pub gravity_scale: f32
+}
+
+impl Default for BodyProperties {
+    fn default() -> Self {
+        Self {
+            mass: 1.0,
+            angular_inertia: 0.0,
+            gravity_scale: 1.0
+        }
+    }
+}
+
+pub struct RigidBodyBuilder {
+    desc: RigidBodyDesc<f32>,
+    properties: BodyProperties
+}
+
+#[allow(dead_code)]
+impl RigidBodyBuilder {
+    // A dynamic body with a mass of 1
+    pub fn new() -> Self {
+        Self {
+            desc: RigidBodyDesc::new().mass(1.0),
+            properties: BodyProperties::default()
+        }
+    }
+
+    pub fn with_status(mut self, status: BodyStatus) -> Self {
+        self.desc.set_status(status);
+        self
+    }
+
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.desc.set_mass(mass);
+        self.properties.mass = mass;
+        self
+    }
+
+    pub fn with_angular_inertia(mut self, angular_inertia: f32) -> Self {
+        self.desc.set_angular_inertia(angular_inertia);
+        self.properties.angular_inertia = angular_inertia;
+        self
+    }
+
+    pub fn with_linear_damping(mut self, damping: f32) -> Self {
+        self.desc.set_linear_damping(damping);
+        self
+    }
+
+    pub fn with_angular_damping(mut self, damping: f32) -> Self {
+        self.desc.set_angular_damping(damping);
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.properties.gravity_scale = gravity_scale;
+        self
+    }
+
+    // Locked bodies aren't rotated by collisions or forces
+    pub fn with_rotation_locked(mut self, locked: bool) -> Self {
+        self.desc.set_rotations_kinematic(locked);
+        self
+    }
+
+    // Bodies built without the PhysicsRes lose their gravity scale unless it is 0 or 1
+    pub fn build(mut self) -> RigidBody<f32> {
+        self.desc.enable_gravity(self.properties.gravity_scale == 1.0);
+        self.desc.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> (World, PhysicsSys) {
+        let mut world = World::new();
+        world.insert(PhysicsRes::new());
+        let mut physics = PhysicsSys::new();
+        System::setup(&mut physics, &mut world);
+        (world, physics)
+    }
+
+    fn spawn(world: &mut World, pos: Vector, body: RigidBodyBuilder, collider: ColliderBuilder) -> Entity {
+        let (rb, col) = {
+            let mut physics = world.write_resource::<PhysicsRes>();
+            let rb = physics.build_rigid_body(body);
+            let col = physics.build_collider(collider, &rb);
+            (rb, col)
+        };
+        world.create_entity().with(TransformCom::new(pos)).with(rb).with(col).build()
+    }
+
+    fn step(world: &mut World, physics: &mut PhysicsSys) -> Vec<CollisionEvent> {
+        world.write_resource::<PhysicsRes>().delta_time = 1.0 / 60.0;
+        physics.run_now(world);
+        world.maintain();
+        world.read_resource::<CollisionEventQueue>().iter().cloned().collect()
+    }
+
+    #[test]
+    fn removed_collider_stops_contacts() {
+        let (mut world, mut physics) = world();
+        let body = || RigidBodyBuilder::new().with_gravity_scale(0.0);
+        let a = spawn(&mut world, Vector::new(0.0, 0.0), body(), ColliderBuilder::ball(1.0, Vector::new(0.0, 0.0)));
+        let b = spawn(&mut world, Vector::new(1.5, 0.0), body(), ColliderBuilder::ball(1.0, Vector::new(0.0, 0.0)));
+        assert!(step(&mut world, &mut physics).iter().any(|e| matches!(e, CollisionEvent::ContactStarted(..))));
+
+        let handle = world.read_storage::<ColliderCom>().get(b).unwrap().0[0];
+        world.write_storage::<ColliderCom>().remove(b);
+        let events = step(&mut world, &mut physics);
+        assert!(events.iter().any(|e| match e {
+            CollisionEvent::ContactStopped(e1, e2) => (*e1, *e2) == (a, b) || (*e1, *e2) == (b, a),
+            _ => false
+        }));
+        assert!(world.read_resource::<PhysicsRes>().collider_entity(handle).is_none());
+    }
+
+    #[test]
+    fn body_properties_exclude_colliders() {
+        let (mut world, mut physics) = world();
+        let entity = spawn(&mut world, Vector::new(0.0, 0.0), RigidBodyBuilder::new().with_mass(2.0).with_gravity_scale(0.5), ColliderBuilder::ball(1.0, Vector::new(0.0, 0.0)).with_density(1.0));
+        step(&mut world, &mut physics);
+
+        let rb = RigidBodyCom(world.read_storage::<RigidBodyCom>().get(entity).unwrap().0);
+        {
+            let mut res = world.write_resource::<PhysicsRes>();
+            let total = res.read_rigid_body(&rb).unwrap().local_inertia().linear;
+            assert!(total > 2.0);
+            assert!(res.read_rigid_body(&rb).unwrap().user_data().is_none());
+
+            res.set_mass(&rb, 3.0);
+            res.set_gravity_scale(&rb, 1.0);
+            let properties = res.body_properties(&rb).unwrap();
+            assert_eq!((properties.mass, properties.gravity_scale), (3.0, 1.0));
+            assert!((res.read_rigid_body(&rb).unwrap().local_inertia().linear - (total + 1.0)).abs() < 1e-4);
+            assert!(res.read_rigid_body(&rb).unwrap().gravity_enabled());
+        }
+
+        world.write_storage::<RigidBodyCom>().remove(entity);
+        step(&mut world, &mut physics);
+        assert!(world.read_resource::<PhysicsRes>().body_properties(&rb).is_none());
+    }
+
+    #[test]
+    fn ray_hits_each_entity_once() {
+        let (mut world, mut physics) = world();
+        let body = RigidBodyBuilder::new().with_status(BodyStatus::Static);
+        let near = spawn(&mut world, Vector::new(2.0, 0.0), body, ColliderBuilder::ball(0.5, Vector::new(0.0, 0.0)));
+        {
+            let mut res = world.write_resource::<PhysicsRes>();
+            let rb = RigidBodyCom(world.read_storage::<RigidBodyCom>().get(near).unwrap().0);
+            let mut colliders = world.write_storage::<ColliderCom>();
+            res.add_collider(ColliderBuilder::ball(0.5, Vector::new(2.0, 0.0)), &rb, colliders.get_mut(near).unwrap());
+        }
+        let far = spawn(&mut world, Vector::new(8.0, 0.0), RigidBodyBuilder::new().with_status(BodyStatus::Static), ColliderBuilder::ball(0.5, Vector::new(0.0, 0.0)));
+        step(&mut world, &mut physics);
+
+        let res = world.read_resource::<PhysicsRes>();
+        let groups = CollisionGroups::new();
+        let hits = res.cast_ray_all(Vector::new(0.0, 0.0), Vector::new(1.0, 0.0), 20.0, &groups);
+        assert_eq!(hits.iter().map(|hit| hit.entity).collect::<Vec<_>>(), vec![near, far]);
+        assert!((hits[0].distance - 1.5).abs() < 1e-4);
+
+        assert!(res.cast_ray_all(Vector::new(0.0, 0.0), Vector::new(0.0, 0.0), 20.0, &groups).is_empty());
+        assert!(res.cast_shape(&Ball::new(0.1), Vector::new(0.0, 0.0), 0.0, Vector::new(0.0, 0.0), 20.0, &groups).is_none());
+        assert_eq!(res.cast_shape(&Ball::new(0.1), Vector::new(0.0, 0.0), 0.0, Vector::new(1.0, 0.0), 20.0, &groups).map(|hit| hit.entity), Some(near));
+    }
+
+    #[test]
+    fn handles_of_components_removed_right_away_are_freed() {
+        let (mut world, mut physics) = world();
+        let entity = spawn(&mut world, Vector::new(0.0, 0.0), RigidBodyBuilder::new(), ColliderBuilder::ball(1.0, Vector::new(0.0, 0.0)));
+        world.write_storage::<RigidBodyCom>().remove(entity);
+        world.write_storage::<ColliderCom>().remove(entity);
+        step(&mut world, &mut physics);
+        step(&mut world, &mut physics);
+
+        let res = world.read_resource::<PhysicsRes>();
+        assert_eq!(res.bodies.iter().count(), 0);
+        assert_eq!(res.colliders.iter().count(), 0);
+    }
+
+    #[test]
+    fn handles_are_kept_until_their_components_are_inserted() {
+        let (mut world, mut physics) = world();
+        let (rb, col) = {
+            let mut res = world.write_resource::<PhysicsRes>();
+            let rb = res.create_rigid_body();
+            let col = res.create_collider_ball(1.0, Vector::new(0.0, 0.0), &rb);
+            (rb, col)
+        };
+        step(&mut world, &mut physics);
+        world.create_entity().with(TransformCom::new(Vector::new(0.0, 0.0))).with(rb).with(col).build();
+        step(&mut world, &mut physics);
+        step(&mut world, &mut physics);
+
+        let res = world.read_resource::<PhysicsRes>();
+        assert_eq!(res.bodies.iter().count(), 1);
+        assert_eq!(res.colliders.iter().count(), 1);
+    }
+
+    #[test]
+    fn removed_collider_exits_sensors() {
+        let (mut world, mut physics) = world();
+        let sensor = spawn(&mut world, Vector::new(0.0, 0.0), RigidBodyBuilder::new().with_status(BodyStatus::Static), ColliderBuilder::ball(2.0, Vector::new(0.0, 0.0)).with_sensor(true));
+        let other = spawn(&mut world, Vector::new(0.5, 0.0), RigidBodyBuilder::new().with_gravity_scale(0.0), ColliderBuilder::ball(0.5, Vector::new(0.0, 0.0)));
+        let entered = (0..3).flat_map(|_| step(&mut world, &mut physics)).any(|e| matches!(e, CollisionEvent::SensorEntered(s, o) if s == sensor && o == other));
+        assert!(entered);
+
+        world.write_storage::<ColliderCom>().remove(other);
+        let events = step(&mut world, &mut physics);
+        assert!(events.iter().any(|e| matches!(e, CollisionEvent::SensorExited(s, o) if *s == sensor && *o == other)));
     }
 }
@@ -1,17 +1,27 @@
 pub mod sdl;
 
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
 use serde::{Serialize, Deserialize};
 
 use nalgebra::Vector2;
 
 use specs::{prelude::*, Component, DenseVecStorage};
+use specs::storage::ComponentEvent;
 
 use invader_macro::DefaultConstructor;
 use crate::physics::TransformCom;
 use crate::render::sdl::SDLRenderImpl;
 use crate::input::{InputEventQueue, InputEvent};
 use crate::input::key::{Key, KeysRes};
+use crate::input::mouse::MouseButton;
+use crate::input::pointer::Pointer;
+use crate::input::gamepad::GamepadButton;
 use crate::misc::{Convertable, Vector};
+use crate::misc::locale::LocaleRes;
 
 #[derive(Debug, DefaultConstructor)]
 pub struct CameraRes {
@@ -30,102 +40,437 @@ impl Default for CameraRes {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UIScaleMode {
+    // The UI is laid out at this resolution and scaled to fit into the screen, keeping the aspect ratio
+    ReferenceResolution(u32, u32),
+    // Scales the UI by the DPI of the display relative to UI_REFERENCE_DPI
+    Dpi,
+    Manual(f32)
+}
+
+// The scale is updated by the RenderSys every frame, UI coordinates are screen coordinates divided by it
+#[derive(Debug)]
+pub struct UIScaleRes {
+    pub mode: UIScaleMode,
+    pub scale: f32
+}
+
+impl UIScaleRes {
+    fn update(&mut self, screen: Vector2<u32>, dpi: Option<f32>) -> f32 {
+        let scale = match self.mode {
+            UIScaleMode::ReferenceResolution(width, height) => (screen.x as f32 / width as f32).min(screen.y as f32 / height as f32),
+            UIScaleMode::Dpi => dpi.map_or(1.0, |dpi| dpi / UI_REFERENCE_DPI),
+            UIScaleMode::Manual(scale) => scale
+        };
+        self.scale = match scale.is_finite() {
+            true => scale.max(UI_MIN_SCALE),
+            false => 1.0
+        };
+        self.scale
+    }
+
+    pub fn to_ui(&self, point: Vector2<i32>) -> Vector2<i32> {
+        Vector2::new((point.x as f32 / self.scale) as i32, (point.y as f32 / self.scale) as i32)
+    }
+}
+
+impl Default for UIScaleRes {
+    fn default() -> Self {
+        Self {
+            mode: UIScaleMode::Manual(1.0),
+            scale: 1.0
+        }
+    }
+}
+
 pub struct RenderSys<'a> {
-    renderer: SDLRenderImpl<'a>
+    renderer: SDLRenderImpl<'a>,
+    pointer: Vector2<i32>,
+    focus_order: Vec<Entity>,
+    overlay: Option<(Vector2<i32>, Vector2<i32>)>,
+    clip: Option<(Vector2<i32>, Vector2<i32>)>,
+    content_sizes: HashMap<Entity, Vector2<u32>>,
+    font_locale: String,
+    style_event_reader: Option<ReaderId<ComponentEvent>>,
+    // One for each kind of widget, in the order of the widget storages in the system data
+    widget_event_readers: Vec<ReaderId<ComponentEvent>>,
+    theme_generation: Option<u64>
 }
 
 impl<'a, 'b> System<'a> for RenderSys<'b> {
-    type SystemData = (Write<'a, UIEventQueue>,
+    type SystemData = (Entities<'a>,
+        Write<'a, UIEventQueue>,
+        Write<'a, UIFocusRes>,
         Read<'a, CameraRes>,
+        Write<'a, UIScaleRes>,
         Read<'a, InputEventQueue>,
         Read<'a, KeysRes>,
         ReadStorage<'a, TransformCom>,
         ReadStorage<'a, SpriteCom>,
         ReadStorage<'a, TextCom>,
-        ReadStorage<'a, ButtonUICom>,
-        ReadStorage<'a, TextUICom>,
+        WriteStorage<'a, ButtonUICom>,
+        WriteStorage<'a, TextUICom>,
         WriteStorage<'a, TextFieldUICom>,
-        ReadStorage<'a, StartVerticalGroupCom>,
-        ReadStorage<'a, StartHorizontalGroupCom>,
-        ReadStorage<'a, EndGroupCom>,
-        ReadStorage<'a, ConstraintCom>);
+        WriteStorage<'a, CheckboxUICom>,
+        WriteStorage<'a, SliderUICom>,
+        WriteStorage<'a, DropdownUICom>,
+        WriteStorage<'a, ProgressBarUICom>,
+        WriteStorage<'a, ImageUICom>,
+        WriteStorage<'a, GroupUICom>,
+        WriteStorage<'a, ScrollUICom>,
+        WriteStorage<'a, UIParentCom>,
+        WriteStorage<'a, ConstraintCom>,
+        ReadStorage<'a, StyleUICom>,
+        Read<'a, UITheme>,
+        Read<'a, LocaleRes>,
+        LegacyGroupStorage<'a>);
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.style_event_reader = Some(world.write_component::<StyleUICom>().register_reader());
+        self.widget_event_readers = vec![
+            world.write_component::<ButtonUICom>().register_reader(),
+            world.write_component::<TextUICom>().register_reader(),
+            world.write_component::<TextFieldUICom>().register_reader(),
+            world.write_component::<CheckboxUICom>().register_reader(),
+            world.write_component::<SliderUICom>().register_reader(),
+            world.write_component::<DropdownUICom>().register_reader(),
+            world.write_component::<ProgressBarUICom>().register_reader(),
+            world.write_component::<ImageUICom>().register_reader(),
+            world.write_component::<GroupUICom>().register_reader(),
+            world.write_component::<ScrollUICom>().register_reader()
+        ];
+    }
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut events, camera, input_events, keys, transforms, sprites, texts, buttons, text_labels, mut text_fields, v_group_start, h_group_start, group_end, constraints) = data;
+        let (entities, mut events, mut focus, camera, mut scale, input_events, keys, transforms, sprites, texts, mut buttons, mut text_labels, mut text_fields, mut checkboxes, mut sliders, mut dropdowns, mut progress_bars, mut images, mut groups, mut scrolls, mut parents, mut constraints, styles, theme, locale, mut legacy_groups) = data;
+
+        convert_legacy_groups(&entities, &mut constraints, &mut parents, &mut groups, &mut scrolls, &mut legacy_groups);
+        delete_orphans(&entities, &parents);
+
+        // Styles are only applied to their widgets when the StyleUICom or the theme changes, or a widget is added after its style
+        let mut restyled = BitSet::new();
+        for event in styles.channel().read(self.style_event_reader.as_mut().unwrap()) {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => { restyled.add(*id); },
+                ComponentEvent::Removed(_) => ()
+            }
+        }
+        {
+            // Widgets are modified every frame while they are laid out, so only insertions count
+            let channels = [buttons.channel(), text_labels.channel(), text_fields.channel(), checkboxes.channel(), sliders.channel(), dropdowns.channel(), progress_bars.channel(), images.channel(), groups.channel(), scrolls.channel()];
+            for (channel, reader) in channels.iter().zip(self.widget_event_readers.iter_mut()) {
+                for event in channel.read(reader) {
+                    if let ComponentEvent::Inserted(id) = event {
+                        restyled.add(*id);
+                    }
+                }
+            }
+        }
+        if self.theme_generation != Some(theme.generation()) {
+            self.theme_generation = Some(theme.generation());
+            for (entity, _) in (&entities, &styles).join() {
+                restyled.add(entity.id());
+            }
+        }
+        for (entity, style, _) in (&entities, &styles, &restyled).join() {
+            let style = theme.resolve(style);
+            if let Some(button) = buttons.get_mut(entity) {
+                restyle(&mut button.sprite, &style.sprite);
+                restyle(&mut button.sprite_hovered, &style.sprite_hovered);
+                restyle(&mut button.sprite_pressed, &style.sprite_pressed);
+                restyle(&mut button.sprite_disabled, &style.sprite_disabled);
+            }
+            if let Some(checkbox) = checkboxes.get_mut(entity) {
+                restyle(&mut checkbox.sprite, &style.sprite);
+                restyle(&mut checkbox.sprite_checked, &style.sprite_checked);
+            }
+            if let Some(image) = images.get_mut(entity) {
+                restyle(&mut image.sprite, &style.sprite);
+            }
+            if let Some(group) = groups.get_mut(entity) {
+                restyle(&mut group.sprite, &style.sprite);
+                group.padding = style.padding.unwrap_or(group.padding);
+            }
+            if let Some(scroll) = scrolls.get_mut(entity) {
+                restyle(&mut scroll.scrollbar, &style.scrollbar);
+                restyle(&mut scroll.scrollbar_handle, &style.scrollbar_handle);
+            }
+            if let Some(text_label) = text_labels.get_mut(entity) {
+                restyle(&mut text_label.font, &style.font);
+                text_label.text_color = style.text_color.or(text_label.text_color);
+            }
+            if let Some(text_field) = text_fields.get_mut(entity) {
+                restyle(&mut text_field.background, &style.background);
+                restyle(&mut text_field.font, &style.font);
+                text_field.text_color = style.text_color.or(text_field.text_color);
+            }
+            if let Some(slider) = sliders.get_mut(entity) {
+                restyle(&mut slider.track, &style.track);
+                restyle(&mut slider.handle, &style.handle);
+            }
+            if let Some(dropdown) = dropdowns.get_mut(entity) {
+                restyle(&mut dropdown.background, &style.background);
+                restyle(&mut dropdown.list_background, &style.list_background);
+                restyle(&mut dropdown.font, &style.font);
+                dropdown.text_color = style.text_color.or(dropdown.text_color);
+            }
+            if let Some(progress_bar) = progress_bars.get_mut(entity) {
+                restyle(&mut progress_bar.background, &style.background);
+                restyle(&mut progress_bar.fill, &style.fill);
+            }
+        }
 
         self.renderer.pre();
 
         for (sprite, transform) in (&sprites, &transforms).join() {
-            self.renderer.render(&sprite.name, transform.pos, sprite.dim, camera.pos, camera.zoom, camera.screen);
+            let dim = Vector::new(sprite.dim.x * transform.scale.x, sprite.dim.y * transform.scale.y);
+            self.renderer.render(&sprite.name, transform.pos, dim, transform.rotation, camera.pos, camera.zoom, camera.screen);
         }
 
         for (text, transform) in (&texts, &transforms).join() {
-            self.renderer.write(&text.text, &text.font, transform.pos, text.dim, camera.pos, camera.zoom, camera.screen);
+            let dim = Vector::new(text.dim.x * transform.scale.x, text.dim.y * transform.scale.y);
+            self.renderer.write(&text.text, &text.font, transform.pos, dim, transform.rotation, camera.pos, camera.zoom, camera.screen);
+        }
+
+        if locale.locale != self.font_locale {
+            self.font_locale = locale.locale.clone();
+            self.renderer.set_font_fallbacks(locale.font_fallbacks());
         }
 
+        // The UI is laid out in scaled units, so pointers have to be mapped into them as well
+        let ui_scale = scale.update(camera.screen, self.renderer.display_dpi());
+        self.renderer.set_scale(ui_scale);
+        let mut scaled_events = InputEventQueue::new();
+        for event in input_events.iter() {
+            scaled_events.push(match event {
+                InputEvent::MouseDown(b, m) => InputEvent::MouseDown(*b, scale.to_ui(*m)),
+                InputEvent::MouseUp(b, m) => InputEvent::MouseUp(*b, scale.to_ui(*m)),
+                InputEvent::MouseMove(m) => InputEvent::MouseMove(scale.to_ui(*m)),
+                InputEvent::PointerDown(p, m, pressure) => InputEvent::PointerDown(*p, scale.to_ui(*m), *pressure),
+                InputEvent::PointerMove(p, m, pressure) => InputEvent::PointerMove(*p, scale.to_ui(*m), *pressure),
+                InputEvent::PointerUp(p, m) => InputEvent::PointerUp(*p, scale.to_ui(*m)),
+                e => e.clone()
+            });
+        }
+        let input_events = scaled_events;
+
         events.clear();
-        let mut container: Vec<(Vector2<i32>, Vector2<u32>, Vector2<i32>, bool)> = Vec::new();
-        container.push((Vector2::new(0, 0), camera.screen, Vector2::new(0, 0), true));
-        for (constraint, button, text_label, text_fields, vgs, hgs, ge) in (&constraints, (&buttons).maybe(), (&text_labels).maybe(), (&mut text_fields).maybe(), (&v_group_start).maybe(), (&h_group_start).maybe(), (&group_end).maybe()).join() {
-            let size = Vector2::new(constraint.x_size.as_pixels(container.last().unwrap().1.x), constraint.y_size.as_pixels(container.last().unwrap().1.y));
-
-            let container_pos: Vector2<i32> = Vector2::new(container.last().unwrap().0.x, container.last().unwrap().0.y).convert();
-            let offset_pos: Vector2<i32> = Vector2::new(container.last().unwrap().2.x, container.last().unwrap().2.y).convert();
-            let local_pos: Vector2<i32> = Vector2::new(constraint.x_pos.as_pixels(size.x, container.last().unwrap().1.x), constraint.y_pos.as_pixels(size.y, container.last().unwrap().1.y)).convert();
-            let global_pos = container_pos + offset_pos + local_pos;
-            let end_pos = global_pos + size.convert();
+        for event in input_events.iter() {
+            match event {
+                // A lifted finger doesn't hover over anything
+                InputEvent::PointerUp(Pointer::Touch(_), _) => self.pointer = Vector2::new(i32::MIN, i32::MIN),
+                InputEvent::PointerMove(_, m, _) | InputEvent::PointerDown(_, m, _) | InputEvent::PointerUp(_, m) => self.pointer = *m,
+                _ => ()
+            }
+        }
 
-            match vgs {
-                Some(vgs) => {
-                    self.renderer.render_ss(&vgs.sprite, global_pos, size);
-                    if container.last_mut().unwrap().3 {
-                        container.last_mut().unwrap().2.y += (size.y as i32) + local_pos.y;
-                    } else {
-                        container.last_mut().unwrap().2.x += (size.x as i32) + local_pos.x;
-                    }
-                    container.push((global_pos, size, Vector2::new(0, 0), true));
-                    continue;
+        // Navigation works on the focus order of the previous frame, as the current one is only known once the layout is done
+        let (horizontal_captured, vertical_captured) = match focus.focused {
+            Some(e) if dropdowns.get(e).map_or(false, |d| d.open) => (true, true),
+            Some(e) if text_fields.contains(e) || sliders.contains(e) => (true, false),
+            _ => (false, false)
+        };
+        let mut activate = false;
+        let mut navigated = false;
+        for event in input_events.iter() {
+            let navigation = match event {
+                InputEvent::KeyDown(Key::Tab, modifiers) | InputEvent::KeyRepeat(Key::Tab, modifiers) if modifiers.shift() => UINavigation::Previous,
+                InputEvent::KeyDown(Key::Tab, _) | InputEvent::KeyRepeat(Key::Tab, _) => UINavigation::Next,
+                InputEvent::KeyDown(Key::Down, _) | InputEvent::KeyRepeat(Key::Down, _) if !vertical_captured => UINavigation::Next,
+                InputEvent::KeyDown(Key::Right, _) | InputEvent::KeyRepeat(Key::Right, _) if !horizontal_captured => UINavigation::Next,
+                InputEvent::KeyDown(Key::Up, _) | InputEvent::KeyRepeat(Key::Up, _) if !vertical_captured => UINavigation::Previous,
+                InputEvent::KeyDown(Key::Left, _) | InputEvent::KeyRepeat(Key::Left, _) if !horizontal_captured => UINavigation::Previous,
+                InputEvent::KeyDown(Key::Return, _) => UINavigation::Activate,
+                InputEvent::KeyDown(Key::Escape, _) => UINavigation::Cancel,
+                InputEvent::GamepadDown(_, GamepadButton::DPadDown) if !vertical_captured => UINavigation::Next,
+                InputEvent::GamepadDown(_, GamepadButton::DPadRight) if !horizontal_captured => UINavigation::Next,
+                InputEvent::GamepadDown(_, GamepadButton::DPadUp) if !vertical_captured => UINavigation::Previous,
+                InputEvent::GamepadDown(_, GamepadButton::DPadLeft) if !horizontal_captured => UINavigation::Previous,
+                InputEvent::GamepadDown(_, GamepadButton::A) => UINavigation::Activate,
+                InputEvent::GamepadDown(_, GamepadButton::B) => UINavigation::Cancel,
+                _ => continue
+            };
+
+            match navigation {
+                UINavigation::Next | UINavigation::Previous if !self.focus_order.is_empty() => {
+                    let len = self.focus_order.len();
+                    let current = focus.focused.and_then(|e| self.focus_order.iter().position(|f| *f == e));
+                    let next = match (current, navigation) {
+                        (Some(i), UINavigation::Next) => (i + 1) % len,
+                        (Some(i), _) => (i + len - 1) % len,
+                        (None, UINavigation::Next) => 0,
+                        (None, _) => len - 1
+                    };
+                    focus.focused = Some(self.focus_order[next]);
+                    navigated = true;
                 },
-                None => ()
+                UINavigation::Activate => activate = true,
+                UINavigation::Cancel => focus.focused = None,
+                _ => ()
             }
+        }
+        let clicked = input_events.iter().any(|e| matches!(e, InputEvent::PointerDown(..)));
+        let mut clicked_focusable = false;
+        let mut text_input = None;
+        let mut open_dropdown = None;
+        self.focus_order.clear();
 
-            match hgs {
-                Some(hgs) => {
-                    self.renderer.render_ss(&hgs.sprite, global_pos, size);
-                    if container.last_mut().unwrap().3 {
-                        container.last_mut().unwrap().2.y += (size.y as i32) + local_pos.y;
-                    } else {
-                        container.last_mut().unwrap().2.x += (size.x as i32) + local_pos.x;
+        let mut wheel_consumed = false;
+        self.content_sizes.retain(|e, _| groups.contains(*e));
+        let mut container: Vec<UIContainer> = Vec::new();
+        container.push(UIContainer::new(None, Vector2::new(0, 0), scale.to_ui(camera.screen.convert()).convert(), true, 0, None));
+        for node in ui_tree(&entities, &constraints, &parents, &groups) {
+            let entity = match node {
+                UINode::Element(entity) => entity,
+                UINode::End => {
+                    if container.len() > 1 {
+                        let ended = container.pop().unwrap();
+                        let content = ended.content_size();
+                        if let Some(entity) = ended.entity {
+                            self.content_sizes.insert(entity, content);
+                        }
+                        if let Some(scroll) = ended.scroll {
+                            self.clip = container.last().unwrap().clip;
+                            self.renderer.set_clip_ss(self.clip.map(clip_rect));
+                            let padding = groups.get(scroll.entity).map_or(UIEdges::default(), |g| g.padding);
+                            let content_size = match ended.vertical {
+                                true => content.y.saturating_add(padding.vertical()),
+                                false => content.x.saturating_add(padding.horizontal())
+                            };
+                            self.scroll(scrolls.get_mut(scroll.entity).unwrap(), ended.vertical, scroll, content_size, &input_events, &mut wheel_consumed);
+                        }
                     }
-                    container.push((global_pos, size, Vector2::new(0, 0), false));
                     continue;
-                },
-                None => ()
+                }
+            };
+            let constraint = constraints.get(entity).unwrap();
+            let content = match constraint.fits_content() {
+                true => self.measure(entity, &groups, &text_labels, &buttons, &checkboxes, &images),
+                false => Vector2::new(0, 0)
+            };
+
+            let parent = container.last().unwrap();
+            let (local_pos, size) = constraint.layout(parent.size, content);
+            let global_pos = parent.pos + parent.cursor() + local_pos;
+            let end_pos = global_pos + size.convert();
+
+            self.clip = parent.clip;
+            self.renderer.set_clip_ss(self.clip.map(clip_rect));
+            let visible = self.clip.map_or(true, |(start, end)| global_pos.x < end.x && start.x < end_pos.x && global_pos.y < end.y && start.y < end_pos.y);
+
+            if navigated && focus.focused == Some(entity) {
+                if let Some(scroll) = container.iter_mut().rev().find_map(|c| c.scroll.as_mut()) {
+                    scroll.focused = Some((global_pos, end_pos));
+                }
             }
 
-            match ge {
-                Some(_) => {
-                    container.pop();
+            match groups.get(entity) {
+                Some(group) => {
+                    if visible {
+                        self.renderer.render_ss(&group.sprite, global_pos, size);
+                    }
+                    container.last_mut().unwrap().advance(size, local_pos, constraint.margin);
+
+                    let inner_pos = global_pos + Vector2::new(group.padding.left, group.padding.top).convert();
+                    let inner_size = Vector2::new(size.x.saturating_sub(group.padding.horizontal()), size.y.saturating_sub(group.padding.vertical()));
+                    match scrolls.get(entity) {
+                        Some(scroll) => {
+                            // Leave room for the scrollbar if the content did not fit during the last frame
+                            let viewport = if group.vertical { size.y } else { size.x };
+                            let mut content_size = inner_size;
+                            let mut content_pos = inner_pos;
+                            if group.vertical {
+                                content_pos.y -= scroll.offset as i32;
+                                if scroll.content_size > viewport {
+                                    content_size.x = content_size.x.saturating_sub(scroll.scrollbar_width);
+                                }
+                            } else {
+                                content_pos.x -= scroll.offset as i32;
+                                if scroll.content_size > viewport {
+                                    content_size.y = content_size.y.saturating_sub(scroll.scrollbar_width);
+                                }
+                            }
+
+                            let mut scroll_container = UIContainer::new(Some(entity), content_pos, content_size, group.vertical, group.spacing, Some(intersect(self.clip, (global_pos, end_pos))));
+                            scroll_container.scroll = Some(UIScroll { entity: entity, pos: global_pos, size: size, focused: None });
+                            container.push(scroll_container);
+                        },
+                        None => container.push(UIContainer::new(Some(entity), inner_pos, inner_size, group.vertical, group.spacing, self.clip))
+                    }
                     continue;
                 },
                 None => ()
             }
 
+            let button = buttons.get_mut(entity);
+            let text_label = text_labels.get(entity);
+            let text_field = text_fields.get_mut(entity);
+            let checkbox = checkboxes.get_mut(entity);
+            let slider = sliders.get_mut(entity);
+            let dropdown = dropdowns.get_mut(entity);
+            let progress_bar = progress_bars.get(entity);
+            let image = images.get(entity);
+
+            if !visible {
+                if button.map_or(false, |b| !b.disabled) || text_field.is_some() || checkbox.is_some() || slider.is_some() || dropdown.is_some() {
+                    self.focus_order.push(entity);
+                }
+                container.last_mut().unwrap().advance(size, local_pos, constraint.margin);
+                continue;
+            }
+
             match button {
                 Some(button) => {
-                    let mut pressed = false;
-                    for event in input_events.iter() {
-                        match event {
-                            InputEvent::MouseDown(m) if global_pos.x < (m.x as i32) && (m.x as i32) < end_pos.x && global_pos.y < (m.y as i32) && (m.y as i32) < end_pos.y => pressed = true,
+                    if button.disabled {
+                        button.state = ButtonState::Disabled;
+                    } else {
+                        if button.state == ButtonState::Disabled {
+                            button.state = ButtonState::Normal;
+                        }
+
+                        self.focus_order.push(entity);
+
+                        for event in input_events.iter() {
+                            match event {
+                                InputEvent::PointerDown(_, m, _) if self.hit(*m, global_pos, end_pos) => {
+                                    button.state = ButtonState::Pressed;
+                                    focus.focused = Some(entity);
+                                    clicked_focusable = true;
+                                    events.push(UIEvent::ButtonPressed { id: button.element_name.clone() });
+                                },
+                                InputEvent::PointerUp(_, m) if button.state == ButtonState::Pressed => {
+                                    if self.hit(*m, global_pos, end_pos) {
+                                        button.state = ButtonState::Hovered;
+                                        events.push(UIEvent::ButtonReleased { id: button.element_name.clone() });
+                                    } else {
+                                        button.state = ButtonState::Normal;
+                                    }
+                                },
+                                _ => ()
+                            }
+                        }
+
+                        let hovered = self.hit(self.pointer, global_pos, end_pos);
+                        match button.state {
+                            ButtonState::Normal if hovered => {
+                                button.state = ButtonState::Hovered;
+                                events.push(UIEvent::ButtonHovered { id: button.element_name.clone() });
+                            },
+                            ButtonState::Hovered if !hovered => button.state = ButtonState::Normal,
                             _ => ()
                         }
+
+                        if activate && focus.focused == Some(entity) {
+                            events.push(UIEvent::ButtonPressed { id: button.element_name.clone() });
+                            events.push(UIEvent::ButtonReleased { id: button.element_name.clone() });
+                        }
                     }
 
-                    if pressed {
-                        events.push(UIEvent::ButtonPressed { id: button.element_name.clone() });
-                        self.renderer.render_ss(&button.sprite_pressed, global_pos, size);
-                    } else {
-                        self.renderer.render_ss(&button.sprite, global_pos, size);
+                    self.renderer.render_ss(button.current_sprite(self.hit(self.pointer, global_pos, end_pos)), global_pos, size);
+                    if let (Some(highlight), true) = (&focus.highlight, focus.focused == Some(entity)) {
+                        self.renderer.render_ss(highlight, global_pos, size);
                     }
                 },
                 None => ()
@@ -133,56 +478,520 @@ impl<'a, 'b> System<'a> for RenderSys<'b> {
 
             match text_label {
                 Some(text_label) => {
-                    self.renderer.write_ss(&text_label.text, &text_label.font, global_pos, size);
+                    self.renderer.write_colored_ss(&text_label.text, &text_label.font, text_label.text_color, global_pos, size);
                 },
                 None => ()
             }
 
-            match text_fields {
+            match text_field {
                 Some(text_field) => {
+                    self.focus_order.push(entity);
+
+                    let shift = keys.pressed(Key::Shift);
+                    let control = keys.pressed(Key::Control);
+                    let text_pos = Vector2::new(global_pos.x - text_field.scroll as i32, global_pos.y);
+                    let mut changed = false;
+                    for event in input_events.iter() {
+                        match event {
+                            InputEvent::PointerDown(_, m, _) if self.hit(*m, global_pos, end_pos) => {
+                                focus.focused = Some(entity);
+                                clicked_focusable = true;
+                                let index = self.text_index_at(text_field, m.x - text_pos.x, size.y);
+                                text_field.move_cursor(index, shift);
+                                text_field.selecting = true;
+                            },
+                            InputEvent::PointerMove(_, m, _) if text_field.selecting => {
+                                let index = self.text_index_at(text_field, m.x - text_pos.x, size.y);
+                                text_field.move_cursor(index, true);
+                            },
+                            InputEvent::PointerUp(..) => text_field.selecting = false,
+                            InputEvent::TextInput(text) if focus.focused == Some(entity) => {
+                                changed |= text_field.insert(text);
+                                text_field.composition = (String::new(), 0);
+                            },
+                            InputEvent::TextEditing(text, start, _) if focus.focused == Some(entity) => {
+                                text_field.composition = (text.clone(), *start as usize);
+                            },
+                            InputEvent::KeyDown(k, _) | InputEvent::KeyRepeat(k, _) if focus.focused == Some(entity) => match k {
+                                Key::Backspace => changed |= text_field.backspace(),
+                                Key::Delete => changed |= text_field.delete(),
+                                Key::Left => text_field.move_left(shift),
+                                Key::Right => text_field.move_right(shift),
+                                Key::Home => text_field.move_cursor(0, shift),
+                                Key::End => text_field.move_cursor(usize::MAX, shift),
+                                Key::A if control => text_field.select_all(),
+                                Key::C if control && !text_field.masked => self.renderer.set_clipboard(&text_field.selected_text()),
+                                Key::X if control && !text_field.masked => {
+                                    self.renderer.set_clipboard(&text_field.selected_text());
+                                    changed |= text_field.delete_selection();
+                                },
+                                Key::V if control => {
+                                    let clipboard = self.renderer.clipboard();
+                                    changed |= text_field.insert(&clipboard);
+                                },
+                                Key::Return => events.push(UIEvent::TextSubmitted { id: text_field.element_name.clone(), text: text_field.text.clone() }),
+                                _ => ()
+                            },
+                            _ => ()
+                        }
+                    }
+                    text_field.captured = focus.focused == Some(entity);
+                    if !text_field.captured {
+                        text_field.composition = (String::new(), 0);
+                    }
+
+                    if changed {
+                        events.push(UIEvent::TextChanged { id: text_field.element_name.clone(), text: text_field.text.clone() });
+                    }
+
+                    // Keep the caret inside the visible part of the field
+                    let display: Vec<char> = text_field.display_text().chars().collect();
+                    let prefix = |end: usize| -> String { display[..end.min(display.len())].iter().collect() };
+                    let caret_x = self.renderer.text_width(&prefix(text_field.cursor), &text_field.font, size.y);
+                    let text_width = self.renderer.text_width(&prefix(display.len()), &text_field.font, size.y);
+                    if caret_x < text_field.scroll {
+                        text_field.scroll = caret_x;
+                    } else if caret_x + TEXT_CARET_WIDTH > text_field.scroll + size.x {
+                        text_field.scroll = (caret_x + TEXT_CARET_WIDTH).saturating_sub(size.x);
+                    }
+                    text_field.scroll = text_field.scroll.min((text_width + TEXT_CARET_WIDTH).saturating_sub(size.x));
+                    let text_pos = Vector2::new(global_pos.x - text_field.scroll as i32, global_pos.y);
+
                     self.renderer.render_ss(&text_field.background, global_pos, size);
-                    if self.renderer.write_ss(&text_field.text, &text_field.font, global_pos, size) {
-                        text_field.text.pop();
+                    self.renderer.set_clip_ss(Some(clip_rect(intersect(self.clip, (global_pos, end_pos)))));
+
+                    if let Some((start, end)) = text_field.selection() {
+                        let start_x = self.renderer.text_width(&prefix(start), &text_field.font, size.y);
+                        let end_x = self.renderer.text_width(&prefix(end), &text_field.font, size.y);
+                        let (r, g, b, a) = TEXT_SELECTION_COLOR;
+                        self.renderer.fill_ss(Vector2::new(text_pos.x + start_x as i32, text_pos.y), Vector2::new(end_x.saturating_sub(start_x), size.y), r, g, b, a);
+                    }
+
+                    let (composition, composition_cursor) = &text_field.composition;
+                    let mut text: String = prefix(text_field.cursor);
+                    text.push_str(composition);
+                    text.extend(display.iter().skip(text_field.cursor));
+                    self.renderer.write_colored_ss(&text, &text_field.font, text_field.text_color, text_pos, size);
+
+                    if text_field.captured {
+                        let caret: String = prefix(text_field.cursor).chars().chain(composition.chars().take(*composition_cursor)).collect();
+                        let caret_pos = Vector2::new(text_pos.x + self.renderer.text_width(&caret, &text_field.font, size.y) as i32, text_pos.y);
+                        let (r, g, b) = text_field.text_color.or_else(|| self.renderer.font_color(&text_field.font)).unwrap_or((255, 255, 255));
+                        self.renderer.fill_ss(caret_pos, Vector2::new(TEXT_CARET_WIDTH, size.y), r, g, b, 255);
+                        text_input = Some((caret_pos, size));
+                    }
+
+                    self.renderer.set_clip_ss(self.clip.map(clip_rect));
+
+                    if let (Some(highlight), true) = (&focus.highlight, text_field.captured) {
+                        self.renderer.render_ss(highlight, global_pos, size);
+                    }
+                },
+                None => ()
+            }
+
+            match checkbox {
+                Some(checkbox) => {
+                    self.focus_order.push(entity);
+
+                    let mut toggled = activate && focus.focused == Some(entity);
+                    for event in input_events.iter() {
+                        match event {
+                            InputEvent::PointerDown(_, m, _) if self.hit(*m, global_pos, end_pos) => {
+                                focus.focused = Some(entity);
+                                clicked_focusable = true;
+                                toggled = !toggled;
+                            },
+                            _ => ()
+                        }
+                    }
+
+                    if toggled {
+                        checkbox.checked = !checkbox.checked;
+                        events.push(UIEvent::Toggled { id: checkbox.element_name.clone(), checked: checkbox.checked });
                     }
 
+                    self.renderer.render_ss(if checkbox.checked { &checkbox.sprite_checked } else { &checkbox.sprite }, global_pos, size);
+                    if let (Some(highlight), true) = (&focus.highlight, focus.focused == Some(entity)) {
+                        self.renderer.render_ss(highlight, global_pos, size);
+                    }
+                },
+                None => ()
+            }
+
+            match slider {
+                Some(slider) => {
+                    self.focus_order.push(entity);
+
+                    let handle_size = Vector2::new(size.x.min(size.y), size.y);
+                    let track_length = (size.x - handle_size.x) as i32;
+                    let previous = slider.value;
                     for event in input_events.iter() {
                         match event {
-                            InputEvent::MouseDown(m) if global_pos.x < (m.x as i32) && (m.x as i32) < end_pos.x && global_pos.y < (m.y as i32) && (m.y as i32) < end_pos.y => {
-                                text_field.captured = true;
+                            InputEvent::PointerDown(_, m, _) if self.hit(*m, global_pos, end_pos) => {
+                                focus.focused = Some(entity);
+                                clicked_focusable = true;
+                                slider.dragging = true;
+                                slider.set_proportion((m.x - global_pos.x - handle_size.x as i32 / 2) as f32 / track_length.max(1) as f32);
                             },
-                            InputEvent::MouseDown(_) => {
-                                text_field.captured = false;
+                            InputEvent::PointerMove(_, m, _) if slider.dragging => {
+                                slider.set_proportion((m.x - global_pos.x - handle_size.x as i32 / 2) as f32 / track_length.max(1) as f32);
                             },
-                            InputEvent::KeyDown(k) if text_field.captured => {
-                                match k {
-                                    Key::Backspace => { text_field.text.pop(); },
-                                    _ => { k.to_char(keys.pressed(Key::Shift)).map(|c| text_field.text.push(c)); }
-                                }
+                            InputEvent::PointerUp(..) => slider.dragging = false,
+                            InputEvent::KeyDown(Key::Left, _) | InputEvent::KeyRepeat(Key::Left, _) | InputEvent::GamepadDown(_, GamepadButton::DPadLeft) if focus.focused == Some(entity) => slider.set_value(slider.value - slider.key_step()),
+                            InputEvent::KeyDown(Key::Right, _) | InputEvent::KeyRepeat(Key::Right, _) | InputEvent::GamepadDown(_, GamepadButton::DPadRight) if focus.focused == Some(entity) => slider.set_value(slider.value + slider.key_step()),
+                            _ => ()
+                        }
+                    }
 
-                                events.push(UIEvent::TextChanged { id: text_field.element_name.clone(), text: text_field.text.clone() });
-                            }
+                    if slider.value != previous {
+                        events.push(UIEvent::ValueChanged { id: slider.element_name.clone(), value: slider.value });
+                    }
+
+                    let handle_pos = Vector2::new(global_pos.x + (track_length as f32 * slider.proportion()) as i32, global_pos.y);
+                    self.renderer.render_ss(&slider.track, global_pos, size);
+                    self.renderer.render_ss(&slider.handle, handle_pos, handle_size);
+                    if let (Some(highlight), true) = (&focus.highlight, focus.focused == Some(entity)) {
+                        self.renderer.render_ss(highlight, global_pos, size);
+                    }
+                },
+                None => ()
+            }
+
+            match dropdown {
+                Some(dropdown) => {
+                    self.focus_order.push(entity);
+
+                    let list_pos = Vector2::new(global_pos.x, end_pos.y);
+                    let list_end = Vector2::new(end_pos.x, end_pos.y + (size.y as usize * dropdown.options.len()) as i32);
+                    let option_at = |y: i32| ((y - list_pos.y) / size.y.max(1) as i32) as usize;
+                    let mut selected = None;
+                    for event in input_events.iter() {
+                        match event {
+                            InputEvent::PointerDown(_, m, _) if dropdown.open && within(*m, list_pos, list_end) => {
+                                focus.focused = Some(entity);
+                                clicked_focusable = true;
+                                selected = Some(option_at(m.y));
+                            },
+                            InputEvent::PointerDown(_, m, _) if self.hit(*m, global_pos, end_pos) => {
+                                focus.focused = Some(entity);
+                                clicked_focusable = true;
+                                dropdown.open = !dropdown.open;
+                                dropdown.highlighted = dropdown.selected;
+                            },
+                            InputEvent::PointerDown(..) => dropdown.open = false,
+                            InputEvent::MouseDown(b, _) if *b != MouseButton::Left => dropdown.open = false,
+                            InputEvent::KeyDown(Key::Up, _) | InputEvent::KeyRepeat(Key::Up, _) | InputEvent::GamepadDown(_, GamepadButton::DPadUp) if dropdown.open && focus.focused == Some(entity) => {
+                                dropdown.highlighted = dropdown.highlighted.saturating_sub(1);
+                            },
+                            InputEvent::KeyDown(Key::Down, _) | InputEvent::KeyRepeat(Key::Down, _) | InputEvent::GamepadDown(_, GamepadButton::DPadDown) if dropdown.open && focus.focused == Some(entity) => {
+                                dropdown.highlighted = (dropdown.highlighted + 1).min(dropdown.options.len().saturating_sub(1));
+                            },
                             _ => ()
                         }
                     }
+
+                    if activate && focus.focused == Some(entity) {
+                        if dropdown.open {
+                            selected = Some(dropdown.highlighted);
+                        } else {
+                            dropdown.open = true;
+                            dropdown.highlighted = dropdown.selected;
+                        }
+                    }
+                    if dropdown.open && within(self.pointer, list_pos, list_end) {
+                        dropdown.highlighted = option_at(self.pointer.y);
+                    }
+
+                    if let Some(index) = selected.filter(|i| *i < dropdown.options.len()) {
+                        dropdown.open = false;
+                        if index != dropdown.selected {
+                            dropdown.selected = index;
+                            events.push(UIEvent::Selected { id: dropdown.element_name.clone(), index: index, value: dropdown.options[index].clone() });
+                        }
+                    }
+                    if focus.focused != Some(entity) {
+                        dropdown.open = false;
+                    }
+
+                    self.renderer.render_ss(&dropdown.background, global_pos, size);
+                    if let Some(option) = dropdown.options.get(dropdown.selected) {
+                        self.renderer.write_colored_ss(option, &dropdown.font, dropdown.text_color, global_pos, size);
+                    }
+                    if let (Some(highlight), true) = (&focus.highlight, focus.focused == Some(entity)) {
+                        self.renderer.render_ss(highlight, global_pos, size);
+                    }
+
+                    if dropdown.open {
+                        open_dropdown = Some((entity, list_pos, list_end));
+                    }
                 },
                 None => ()
             }
 
-            if container.last_mut().unwrap().3 {
-                container.last_mut().unwrap().2.y += (size.y as i32) + local_pos.y;
-            } else {
-                container.last_mut().unwrap().2.x += (size.x as i32) + local_pos.x;
+            match progress_bar {
+                Some(progress_bar) => {
+                    let fill_size = Vector2::new((size.x as f32 * progress_bar.progress.max(0.0).min(1.0)) as u32, size.y);
+                    self.renderer.render_ss(&progress_bar.background, global_pos, size);
+                    self.renderer.render_ss(&progress_bar.fill, global_pos, fill_size);
+                },
+                None => ()
+            }
+
+            match image {
+                Some(image) => self.renderer.render_ss(&image.sprite, global_pos, size),
+                None => ()
+            }
+
+            container.last_mut().unwrap().advance(size, local_pos, constraint.margin);
+        }
+        self.clip = None;
+        self.renderer.set_clip_ss(None);
+
+        // Open dropdowns are drawn on top of everything else and block the input to the widgets below them
+        self.overlay = None;
+        if let Some((entity, list_pos, list_end)) = open_dropdown {
+            let dropdown = dropdowns.get(entity).unwrap();
+            let option_size = Vector2::new((list_end.x - list_pos.x) as u32, ((list_end.y - list_pos.y) as usize / dropdown.options.len().max(1)) as u32);
+            for (i, option) in dropdown.options.iter().enumerate() {
+                let option_pos = Vector2::new(list_pos.x, list_pos.y + (option_size.y as usize * i) as i32);
+                self.renderer.render_ss(&dropdown.list_background, option_pos, option_size);
+                if i == dropdown.highlighted {
+                    let (r, g, b, a) = TEXT_SELECTION_COLOR;
+                    self.renderer.fill_ss(option_pos, option_size, r, g, b, a);
+                }
+                self.renderer.write_colored_ss(option, &dropdown.font, dropdown.text_color, option_pos, option_size);
             }
+            self.overlay = Some((list_pos, list_end));
         }
 
+        if clicked && !clicked_focusable {
+            focus.focused = None;
+        }
+        self.renderer.set_text_input(text_input);
+        self.renderer.set_scale(1.0);
+
         self.renderer.post();
     }
 }
 
+const TEXT_CARET_WIDTH: u32 = 2;
+const TEXT_SELECTION_COLOR: (u8, u8, u8, u8) = (80, 120, 200, 128);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum UINavigation {
+    Next,
+    Previous,
+    Activate,
+    Cancel
+}
+
+const UI_SCROLL_STEP: i32 = 20;
+const UI_REFERENCE_DPI: f32 = 96.0;
+const UI_MIN_SCALE: f32 = 0.1;
+
+// A group that is currently being laid out, children are placed one after another starting at pos + offset
+struct UIContainer {
+    pos: Vector2<i32>,
+    size: Vector2<u32>,
+    offset: Vector2<i32>,
+    content: Vector2<i32>,
+    vertical: bool,
+    spacing: u32,
+    placed: bool,
+    clip: Option<(Vector2<i32>, Vector2<i32>)>,
+    entity: Option<Entity>,
+    scroll: Option<UIScroll>
+}
+
+impl UIContainer {
+    fn new(entity: Option<Entity>, pos: Vector2<i32>, size: Vector2<u32>, vertical: bool, spacing: u32, clip: Option<(Vector2<i32>, Vector2<i32>)>) -> Self {
+        Self {
+            pos: pos,
+            size: size,
+            offset: Vector2::new(0, 0),
+            content: Vector2::new(0, 0),
+            vertical: vertical,
+            spacing: spacing,
+            placed: false,
+            clip: clip,
+            entity: entity,
+            scroll: None
+        }
+    }
+
+    // Where the next element starts, leaving a gap after the previous one
+    fn cursor(&self) -> Vector2<i32> {
+        let spacing = if self.placed { self.spacing.min(i32::MAX as u32) as i32 } else { 0 };
+        match self.vertical {
+            true => Vector2::new(self.offset.x, self.offset.y.saturating_add(spacing)),
+            false => Vector2::new(self.offset.x.saturating_add(spacing), self.offset.y)
+        }
+    }
+
+    fn advance(&mut self, size: Vector2<u32>, local_pos: Vector2<i32>, margin: UIEdges) {
+        let cursor = self.cursor();
+        let end_x = cursor.x.saturating_add(local_pos.x).saturating_add(size.x.saturating_add(margin.right).min(i32::MAX as u32) as i32);
+        let end_y = cursor.y.saturating_add(local_pos.y).saturating_add(size.y.saturating_add(margin.bottom).min(i32::MAX as u32) as i32);
+        if self.vertical {
+            self.offset.y = end_y;
+        } else {
+            self.offset.x = end_x;
+        }
+        self.content = Vector2::new(self.content.x.max(end_x), self.content.y.max(end_y));
+        self.placed = true;
+    }
+
+    fn content_size(&self) -> Vector2<u32> {
+        Vector2::new(self.content.x.max(0) as u32, self.content.y.max(0) as u32)
+    }
+}
+
+struct UIScroll {
+    entity: Entity,
+    pos: Vector2<i32>,
+    size: Vector2<u32>,
+    focused: Option<(Vector2<i32>, Vector2<i32>)>
+}
+
+fn intersect(clip: Option<(Vector2<i32>, Vector2<i32>)>, (start, end): (Vector2<i32>, Vector2<i32>)) -> (Vector2<i32>, Vector2<i32>) {
+    match clip {
+        Some((clip_start, clip_end)) => {
+            let start = Vector2::new(start.x.max(clip_start.x), start.y.max(clip_start.y));
+            let end = Vector2::new(end.x.min(clip_end.x).max(start.x), end.y.min(clip_end.y).max(start.y));
+            (start, end)
+        },
+        None => (start, end)
+    }
+}
+
+fn clip_rect((start, end): (Vector2<i32>, Vector2<i32>)) -> (Vector2<i32>, Vector2<u32>) {
+    (start, (end - start).convert())
+}
+
+fn within(point: Vector2<i32>, start: Vector2<i32>, end: Vector2<i32>) -> bool {
+    start.x < point.x && point.x < end.x && start.y < point.y && point.y < end.y
+}
+
 impl<'a> RenderSys<'a> {
+    // Hit test for widgets that lie below the overlay of the previous frame
+    fn hit(&self, point: Vector2<i32>, start: Vector2<i32>, end: Vector2<i32>) -> bool {
+        within(point, start, end)
+            && self.clip.map_or(true, |(clip_start, clip_end)| within(point, clip_start, clip_end))
+            && self.overlay.map_or(true, |(overlay_start, overlay_end)| !within(point, overlay_start, overlay_end))
+    }
+
+    // Applies wheel and scrollbar input to a scroll group once its content has been laid out and draws the scrollbar
+    fn scroll(&mut self, group: &mut ScrollUICom, vertical: bool, scroll: UIScroll, content_size: u32, input_events: &InputEventQueue, wheel_consumed: &mut bool) {
+        let viewport_start = scroll.pos;
+        let viewport_end = scroll.pos + scroll.size.convert();
+        let along = |v: Vector2<i32>| if vertical { v.y } else { v.x };
+        let viewport = along(scroll.size.convert()) as u32;
+        group.content_size = content_size;
+        let max_offset = content_size.saturating_sub(viewport);
+
+        let handle_length = match content_size {
+            0 => viewport,
+            _ => ((viewport as u64 * viewport as u64 / content_size as u64) as u32).max(group.scrollbar_width).min(viewport)
+        };
+        let handle_travel = (viewport - handle_length).max(1);
+        let (bar_pos, bar_size) = match vertical {
+            true => (Vector2::new(viewport_end.x - group.scrollbar_width as i32, viewport_start.y), Vector2::new(group.scrollbar_width, viewport)),
+            false => (Vector2::new(viewport_start.x, viewport_end.y - group.scrollbar_width as i32), Vector2::new(viewport, group.scrollbar_width))
+        };
+        let bar_end = bar_pos + bar_size.convert();
+
+        for event in input_events.iter() {
+            match event {
+                InputEvent::MouseWheel(w) if !*wheel_consumed && max_offset > 0 && self.hit(self.pointer, viewport_start, viewport_end) => {
+                    let delta = if vertical { w.y } else { -w.x } * UI_SCROLL_STEP;
+                    group.offset = (group.offset as i32 - delta).max(0) as u32;
+                    *wheel_consumed = true;
+                },
+                InputEvent::PointerDown(_, m, _) if max_offset > 0 && self.hit(*m, bar_pos, bar_end) => {
+                    let handle_pos = (group.offset as u64 * handle_travel as u64 / max_offset as u64) as i32;
+                    let grab = along(*m - bar_pos) - handle_pos;
+                    group.dragging = Some(match grab >= 0 && grab < handle_length as i32 {
+                        true => grab,
+                        false => handle_length as i32 / 2
+                    });
+                },
+                _ => ()
+            }
+
+            match (event, group.dragging) {
+                (InputEvent::PointerDown(_, m, _), Some(grab)) | (InputEvent::PointerMove(_, m, _), Some(grab)) => {
+                    let handle_pos = (along(*m - bar_pos) - grab).max(0) as u64;
+                    group.offset = (handle_pos * max_offset as u64 / handle_travel as u64) as u32;
+                },
+                (InputEvent::PointerUp(..), _) => group.dragging = None,
+                _ => ()
+            }
+        }
+
+        // Bring elements that were focused with the keyboard into view
+        if let Some((focused_start, focused_end)) = scroll.focused {
+            let start = along(focused_start - viewport_start) + group.offset as i32;
+            let end = along(focused_end - viewport_start) + group.offset as i32;
+            if start < group.offset as i32 {
+                group.offset = start.max(0) as u32;
+            } else if end > (group.offset + viewport) as i32 {
+                group.offset = (end - viewport as i32).max(0) as u32;
+            }
+        }
+        group.offset = group.offset.min(max_offset);
+
+        if max_offset > 0 {
+            let handle_offset = (group.offset as u64 * handle_travel as u64 / max_offset as u64) as i32;
+            let (handle_pos, handle_size) = match vertical {
+                true => (Vector2::new(bar_pos.x, bar_pos.y + handle_offset), Vector2::new(group.scrollbar_width, handle_length)),
+                false => (Vector2::new(bar_pos.x + handle_offset, bar_pos.y), Vector2::new(handle_length, group.scrollbar_width))
+            };
+            self.renderer.render_ss(&group.scrollbar, bar_pos, bar_size);
+            self.renderer.render_ss(&group.scrollbar_handle, handle_pos, handle_size);
+        }
+    }
+
+    // Natural size of an element for FitContent constraints, groups use the size of their content during the last frame
+    fn measure(&self, entity: Entity, groups: &WriteStorage<GroupUICom>, text_labels: &WriteStorage<TextUICom>, buttons: &WriteStorage<ButtonUICom>, checkboxes: &WriteStorage<CheckboxUICom>, images: &WriteStorage<ImageUICom>) -> Vector2<u32> {
+        if let Some(group) = groups.get(entity) {
+            let content = self.content_sizes.get(&entity).copied().unwrap_or_else(|| Vector2::new(0, 0));
+            Vector2::new(content.x.saturating_add(group.padding.horizontal()), content.y.saturating_add(group.padding.vertical()))
+        } else if let Some(text) = text_labels.get(entity) {
+            self.renderer.text_size(&text.text, &text.font)
+        } else if let Some(sprite) = buttons.get(entity).map(|b| &b.sprite).or_else(|| checkboxes.get(entity).map(|c| &c.sprite)).or_else(|| images.get(entity).map(|i| &i.sprite)) {
+            self.renderer.sprite_size(sprite)
+        } else {
+            Vector2::new(0, 0)
+        }
+    }
+
+    // Finds the character boundary closest to a horizontal offset from the start of the text
+    fn text_index_at(&self, text_field: &TextFieldUICom, x: i32, height: u32) -> usize {
+        let display: Vec<char> = text_field.display_text().chars().collect();
+        let mut previous = 0;
+        for i in 1..=display.len() {
+            let width = self.renderer.text_width(&display[..i].iter().collect::<String>(), &text_field.font, height) as i32;
+            if x < (previous + width) / 2 {
+                return i - 1;
+            }
+            previous = width;
+        }
+        display.len()
+    }
+
     pub fn new(render: SDLRenderImpl<'a>) -> Self {
         Self {
-            renderer: render
+            renderer: render,
+            pointer: Vector2::new(-1, -1),
+            focus_order: Vec::new(),
+            overlay: None,
+            clip: None,
+            content_sizes: HashMap::new(),
+            font_locale: String::new(),
+            style_event_reader: None,
+            widget_event_readers: Vec::new(),
+            theme_generation: None
         }
     }
 }
@@ -223,77 +1032,733 @@ impl TextCom {
     }
 }
 
+#[derive(Debug, Default)]
+pub struct UIFocusRes {
+    pub focused: Option<Entity>,
+    pub highlight: Option<String>
+}
+
 event_queue! {
     UIEventQueue: pub enum UIEvent {
+        // Sent as soon as the pointer goes down on the button, even if the press is then dragged off it
         ButtonPressed { id: String },
-        TextChanged { id: String, text: String }
+        // The click, only sent when the press ends inside the button or the focused button is activated
+        ButtonReleased { id: String },
+        ButtonHovered { id: String },
+        TextChanged { id: String, text: String },
+        TextSubmitted { id: String, text: String },
+        Toggled { id: String, checked: bool },
+        ValueChanged { id: String, value: f32 },
+        Selected { id: String, index: usize, value: String }
     }
 }
 
-#[derive(Component, Debug)]
-#[storage(DenseVecStorage)]
+#[derive(Debug)]
 pub struct TextUICom {
     pub text: String,
-    pub font: String
+    pub font: String,
+    pub text_color: Option<(u8, u8, u8)>
+}
+
+impl Component for TextUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
 impl TextUICom {
     pub fn new(text: &str, font: &str) -> Self {
         Self {
             text: text.to_string(),
-            font: font.to_string()
+            font: font.to_string(),
+            text_color: None
         }
     }
 }
 
-#[derive(Component, Debug)]
-#[storage(DenseVecStorage)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ButtonState {
+    Normal,
+    Hovered,
+    Pressed,
+    Disabled
+}
+
+#[derive(Debug)]
 pub struct ButtonUICom {
     pub sprite: String,
+    pub sprite_hovered: String,
     pub sprite_pressed: String,
-    pub element_name: String
+    pub sprite_disabled: String,
+    pub element_name: String,
+    pub disabled: bool,
+    pub state: ButtonState
+}
+
+impl Component for ButtonUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
 impl ButtonUICom {
-    pub fn new(sprite: &str, sprite_pressed: &str, element_name: &str) -> Self {
+    pub fn new(sprite: &str, sprite_hovered: &str, sprite_pressed: &str, sprite_disabled: &str, element_name: &str) -> Self {
         Self {
             sprite: sprite.to_string(),
+            sprite_hovered: sprite_hovered.to_string(),
             sprite_pressed: sprite_pressed.to_string(),
-            element_name: element_name.to_string()
+            sprite_disabled: sprite_disabled.to_string(),
+            element_name: element_name.to_string(),
+            disabled: false,
+            state: ButtonState::Normal
+        }
+    }
+
+    // A pressed button that the cursor has been dragged off of is drawn as released, as letting go there will not fire it
+    fn current_sprite(&self, hovered: bool) -> &str {
+        match self.state {
+            ButtonState::Normal => &self.sprite,
+            ButtonState::Hovered => &self.sprite_hovered,
+            ButtonState::Pressed if hovered => &self.sprite_pressed,
+            ButtonState::Pressed => &self.sprite,
+            ButtonState::Disabled => &self.sprite_disabled
         }
     }
 }
 
-#[derive(Component, Debug)]
-#[storage(DenseVecStorage)]
+#[derive(Debug)]
 pub struct TextFieldUICom {
     pub background: String,
     pub text: String,
     pub font: String,
+    pub text_color: Option<(u8, u8, u8)>,
     pub element_name: String,
-    pub captured: bool
+    pub captured: bool,
+    pub max_length: Option<usize>,
+    pub masked: bool,
+    pub cursor: usize,
+    pub anchor: usize,
+    pub scroll: u32,
+    composition: (String, usize),
+    selecting: bool
+}
+
+impl Component for TextFieldUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
 }
 
 impl TextFieldUICom {
     pub fn new(background: &str, text: &str, font: &str, element_name: &str) -> Self {
+        let len = text.chars().count();
         Self {
             background: background.to_string(),
             text: text.to_string(),
             font: font.to_string(),
+            text_color: None,
             element_name: element_name.to_string(),
-            captured: false
+            captured: false,
+            max_length: None,
+            masked: false,
+            cursor: len,
+            anchor: len,
+            scroll: 0,
+            composition: (String::new(), 0),
+            selecting: false
         }
     }
-}
 
-#[derive(Component, Debug, DefaultConstructor)]
-#[storage(DenseVecStorage)]
-pub struct ConstraintCom {
-    x_pos: PositionConstraint,
-    y_pos: PositionConstraint,
-    x_size: SizeConstraint,
-    y_size: SizeConstraint
-}
+    pub fn display_text(&self) -> String {
+        match self.masked {
+            true => "*".repeat(self.text.chars().count()),
+            false => self.text.clone()
+        }
+    }
+
+    pub fn selection(&self) -> Option<(usize, usize)> {
+        match self.cursor.cmp(&self.anchor) {
+            Ordering::Less => Some((self.cursor, self.anchor)),
+            Ordering::Greater => Some((self.anchor, self.cursor)),
+            Ordering::Equal => None
+        }
+    }
+
+    pub fn selected_text(&self) -> String {
+        match self.selection() {
+            Some((start, end)) => self.text.chars().skip(start).take(end - start).collect(),
+            None => String::new()
+        }
+    }
+
+    pub fn move_cursor(&mut self, to: usize, select: bool) {
+        self.cursor = to.min(self.text.chars().count());
+        if !select {
+            self.anchor = self.cursor;
+        }
+    }
+
+    // Without select, an active selection collapses to its start instead of moving the caret
+    pub fn move_left(&mut self, select: bool) {
+        match (self.selection(), select) {
+            (Some((start, _)), false) => self.move_cursor(start, false),
+            _ => self.move_cursor(self.cursor.saturating_sub(1), select)
+        }
+    }
+
+    // Without select, an active selection collapses to its end instead of moving the caret
+    pub fn move_right(&mut self, select: bool) {
+        match (self.selection(), select) {
+            (Some((_, end)), false) => self.move_cursor(end, false),
+            _ => self.move_cursor(self.cursor + 1, select)
+        }
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = 0;
+        self.cursor = self.text.chars().count();
+    }
+
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some((start, end)) => {
+                let (start_byte, end_byte) = (self.byte_index(start), self.byte_index(end));
+                self.text.replace_range(start_byte..end_byte, "");
+                self.move_cursor(start, false);
+                true
+            },
+            None => false
+        }
+    }
+
+    // Replaces the selection, control characters are dropped and the text is cut off at the maximum length
+    pub fn insert(&mut self, text: &str) -> bool {
+        let deleted = self.delete_selection();
+        let space = self.max_length.map_or(usize::MAX, |max| max.saturating_sub(self.text.chars().count()));
+        let text: String = text.chars().filter(|c| !c.is_control()).take(space).collect();
+        if text.is_empty() {
+            return deleted;
+        }
+
+        let byte = self.byte_index(self.cursor);
+        self.text.insert_str(byte, &text);
+        self.move_cursor(self.cursor + text.chars().count(), false);
+        true
+    }
+
+    pub fn backspace(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+        if self.cursor == 0 {
+            return false;
+        }
+        self.anchor = self.cursor - 1;
+        self.delete_selection()
+    }
+
+    pub fn delete(&mut self) -> bool {
+        if self.delete_selection() {
+            return true;
+        }
+        if self.cursor >= self.text.chars().count() {
+            return false;
+        }
+        self.anchor = self.cursor + 1;
+        self.delete_selection()
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text.char_indices().nth(char_index).map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckboxUICom {
+    pub sprite: String,
+    pub sprite_checked: String,
+    pub element_name: String,
+    pub checked: bool
+}
+
+impl Component for CheckboxUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl CheckboxUICom {
+    pub fn new(sprite: &str, sprite_checked: &str, element_name: &str, checked: bool) -> Self {
+        Self {
+            sprite: sprite.to_string(),
+            sprite_checked: sprite_checked.to_string(),
+            element_name: element_name.to_string(),
+            checked: checked
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SliderUICom {
+    pub track: String,
+    pub handle: String,
+    pub element_name: String,
+    pub min: f32,
+    pub max: f32,
+    pub step: f32,
+    pub value: f32,
+    dragging: bool
+}
+
+impl Component for SliderUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl SliderUICom {
+    pub fn new(track: &str, handle: &str, element_name: &str, min: f32, max: f32, value: f32) -> Self {
+        let mut slider = Self {
+            track: track.to_string(),
+            handle: handle.to_string(),
+            element_name: element_name.to_string(),
+            min: min,
+            max: max,
+            step: 0.0,
+            value: min,
+            dragging: false
+        };
+        slider.set_value(value);
+        slider
+    }
+
+    // Clamps the value to the range and snaps it to the step, if there is one
+    pub fn set_value(&mut self, value: f32) {
+        let value = match self.step > 0.0 {
+            true => self.min + ((value - self.min) / self.step).round() * self.step,
+            false => value
+        };
+        self.value = value.max(self.min).min(self.max);
+    }
+
+    pub fn proportion(&self) -> f32 {
+        match self.max > self.min {
+            true => (self.value - self.min) / (self.max - self.min),
+            false => 0.0
+        }
+    }
+
+    pub fn set_proportion(&mut self, proportion: f32) {
+        self.set_value(self.min + proportion * (self.max - self.min));
+    }
+
+    fn key_step(&self) -> f32 {
+        match self.step > 0.0 {
+            true => self.step,
+            false => (self.max - self.min) / 10.0
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DropdownUICom {
+    pub background: String,
+    pub list_background: String,
+    pub font: String,
+    pub text_color: Option<(u8, u8, u8)>,
+    pub element_name: String,
+    pub options: Vec<String>,
+    pub selected: usize,
+    pub open: bool,
+    highlighted: usize
+}
+
+impl Component for DropdownUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl DropdownUICom {
+    pub fn new(background: &str, list_background: &str, font: &str, element_name: &str, options: &[&str], selected: usize) -> Self {
+        Self {
+            background: background.to_string(),
+            list_background: list_background.to_string(),
+            font: font.to_string(),
+            text_color: None,
+            element_name: element_name.to_string(),
+            options: options.iter().map(|o| o.to_string()).collect(),
+            selected: selected,
+            open: false,
+            highlighted: selected
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgressBarUICom {
+    pub background: String,
+    pub fill: String,
+    pub progress: f32
+}
+
+impl Component for ProgressBarUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl ProgressBarUICom {
+    pub fn new(background: &str, fill: &str, progress: f32) -> Self {
+        Self {
+            background: background.to_string(),
+            fill: fill.to_string(),
+            progress: progress
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageUICom {
+    pub sprite: String
+}
+
+impl Component for ImageUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl ImageUICom {
+    pub fn new(sprite: &str) -> Self {
+        Self {
+            sprite: sprite.to_string()
+        }
+    }
+}
+
+// Takes the sprites, fonts and padding of a widget from a style of the theme, the overrides take precedence over the style
+// The style owns the fields it sets, they are overwritten whenever the StyleUICom or the theme changes, so change those rather than the widget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleUICom {
+    pub style: String,
+    pub overrides: UIStyle
+}
+
+impl Component for StyleUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl StyleUICom {
+    pub fn new(style: &str) -> Self {
+        Self {
+            style: style.to_string(),
+            overrides: UIStyle::default()
+        }
+    }
+
+    pub fn with_overrides(mut self, overrides: UIStyle) -> Self {
+        self.overrides = overrides;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UITheme {
+    styles: HashMap<String, UIStyle>,
+    // Changes along with the styles and is unique across themes, so the RenderSys knows when to apply them again
+    #[serde(skip, default = "next_theme_generation")]
+    generation: u64
+}
+
+fn next_theme_generation() -> u64 {
+    static GENERATION: AtomicU64 = AtomicU64::new(0);
+    GENERATION.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+impl UITheme {
+    pub fn new() -> Self {
+        Self {
+            styles: HashMap::new(),
+            generation: next_theme_generation()
+        }
+    }
+
+    pub fn add_style(&mut self, name: &str, style: UIStyle) {
+        self.styles.insert(name.to_string(), style);
+        self.generation = next_theme_generation();
+    }
+
+    pub fn remove_style(&mut self, name: &str) {
+        self.styles.remove(name);
+        self.generation = next_theme_generation();
+    }
+
+    pub fn style(&self, name: &str) -> Option<&UIStyle> {
+        self.styles.get(name)
+    }
+
+    pub fn extend(&mut self, theme: UITheme) {
+        self.styles.extend(theme.styles);
+        self.generation = next_theme_generation();
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn resolve(&self, style: &StyleUICom) -> UIStyle {
+        match self.styles.get(&style.style) {
+            Some(base) => base.merge(&style.overrides),
+            None => style.overrides.clone()
+        }
+    }
+
+    pub fn into_file(&self, file: &str) {
+        let file = File::create(file).unwrap();
+        bincode::serialize_into(&file, &self).unwrap();
+    }
+
+    // Missing and malformed files both give None, so a stray theme next to a sprite sheet is ignored
+    pub fn from_file(file: &str) -> Option<Self> {
+        let file = File::open(file).ok()?;
+        bincode::deserialize_from(&file).ok()
+    }
+}
+
+impl Default for UITheme {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Each widget only uses the fields that apply to it, unset fields leave the widget as it is
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UIStyle {
+    pub sprite: Option<String>,
+    pub sprite_hovered: Option<String>,
+    pub sprite_pressed: Option<String>,
+    pub sprite_disabled: Option<String>,
+    pub sprite_checked: Option<String>,
+    pub background: Option<String>,
+    pub list_background: Option<String>,
+    pub track: Option<String>,
+    pub handle: Option<String>,
+    pub fill: Option<String>,
+    pub scrollbar: Option<String>,
+    pub scrollbar_handle: Option<String>,
+    pub font: Option<String>,
+    pub text_color: Option<(u8, u8, u8)>,
+    pub padding: Option<UIEdges>
+}
+
+impl UIStyle {
+    pub fn merge(&self, overrides: &UIStyle) -> UIStyle {
+        UIStyle {
+            sprite: overrides.sprite.clone().or_else(|| self.sprite.clone()),
+            sprite_hovered: overrides.sprite_hovered.clone().or_else(|| self.sprite_hovered.clone()),
+            sprite_pressed: overrides.sprite_pressed.clone().or_else(|| self.sprite_pressed.clone()),
+            sprite_disabled: overrides.sprite_disabled.clone().or_else(|| self.sprite_disabled.clone()),
+            sprite_checked: overrides.sprite_checked.clone().or_else(|| self.sprite_checked.clone()),
+            background: overrides.background.clone().or_else(|| self.background.clone()),
+            list_background: overrides.list_background.clone().or_else(|| self.list_background.clone()),
+            track: overrides.track.clone().or_else(|| self.track.clone()),
+            handle: overrides.handle.clone().or_else(|| self.handle.clone()),
+            fill: overrides.fill.clone().or_else(|| self.fill.clone()),
+            scrollbar: overrides.scrollbar.clone().or_else(|| self.scrollbar.clone()),
+            scrollbar_handle: overrides.scrollbar_handle.clone().or_else(|| self.scrollbar_handle.clone()),
+            font: overrides.font.clone().or_else(|| self.font.clone()),
+            text_color: overrides.text_color.or(self.text_color),
+            padding: overrides.padding.or(self.padding)
+        }
+    }
+}
+
+fn restyle(target: &mut String, value: &Option<String>) {
+    if let Some(value) = value {
+        if target != value {
+            *target = value.clone();
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct ConstraintCom {
+    x_pos: PositionConstraint,
+    y_pos: PositionConstraint,
+    x_size: SizeConstraint,
+    y_size: SizeConstraint,
+    margin: UIEdges,
+    min_size: (u32, u32),
+    max_size: (u32, u32),
+    aspect_ratio: Option<f32>
+}
+
+impl ConstraintCom {
+    pub fn new(x_pos: PositionConstraint, y_pos: PositionConstraint, x_size: SizeConstraint, y_size: SizeConstraint) -> Self {
+        Self {
+            x_pos: x_pos,
+            y_pos: y_pos,
+            x_size: x_size,
+            y_size: y_size,
+            margin: UIEdges::default(),
+            min_size: (0, 0),
+            max_size: (u32::MAX, u32::MAX),
+            aspect_ratio: None
+        }
+    }
+
+    pub fn with_margin(mut self, margin: UIEdges) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_min_size(mut self, width: u32, height: u32) -> Self {
+        self.min_size = (width, height);
+        self
+    }
+
+    pub fn with_max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = (width, height);
+        self
+    }
+
+    // Width divided by height, the element is shrunk along one axis to match it
+    pub fn with_aspect_ratio(mut self, ratio: f32) -> Self {
+        self.aspect_ratio = Some(ratio);
+        self
+    }
+
+    fn fits_content(&self) -> bool {
+        matches!(self.x_size, SizeConstraint::FitContent) || matches!(self.y_size, SizeConstraint::FitContent)
+    }
+
+    // Position relative to the cursor of the container and size of the element, the margins are kept clear within the container
+    fn layout(&self, container_size: Vector2<u32>, content_size: Vector2<u32>) -> (Vector2<i32>, Vector2<u32>) {
+        let available = Vector2::new(container_size.x.saturating_sub(self.margin.horizontal()), container_size.y.saturating_sub(self.margin.vertical()));
+        let mut size = Vector2::new(self.x_size.as_pixels(available.x, content_size.x), self.y_size.as_pixels(available.y, content_size.y));
+
+        if let Some(ratio) = self.aspect_ratio.filter(|r| *r > 0.0) {
+            let width = (size.y as f32 * ratio) as u32;
+            if width <= size.x {
+                size.x = width;
+            } else {
+                size.y = (size.x as f32 / ratio) as u32;
+            }
+        }
+
+        // The minimum wins if it is larger than the maximum
+        let limit = i32::MAX as u32;
+        size.x = size.x.min(self.max_size.0).max(self.min_size.0).min(limit);
+        size.y = size.y.min(self.max_size.1).max(self.min_size.1).min(limit);
+
+        let x = self.x_pos.as_pixels(size.x, available.x).saturating_add(self.margin.left).min(limit);
+        let y = self.y_pos.as_pixels(size.y, available.y).saturating_add(self.margin.top).min(limit);
+        (Vector2::new(x as i32, y as i32), size)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UIEdges {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32
+}
+
+impl UIEdges {
+    pub fn new(left: u32, right: u32, top: u32, bottom: u32) -> Self {
+        Self {
+            left: left,
+            right: right,
+            top: top,
+            bottom: bottom
+        }
+    }
+
+    pub fn uniform(size: u32) -> Self {
+        Self::new(size, size, size, size)
+    }
+
+    fn horizontal(&self) -> u32 {
+        self.left.saturating_add(self.right)
+    }
+
+    fn vertical(&self) -> u32 {
+        self.top.saturating_add(self.bottom)
+    }
+}
+
+// Elements are deleted along with their parent
+#[derive(Component, Debug, Clone, Copy)]
+#[storage(DenseVecStorage)]
+pub struct UIParentCom {
+    pub parent: Option<Entity>,
+    pub order: i32
+}
+
+impl UIParentCom {
+    pub fn new(parent: Entity, order: i32) -> Self {
+        Self {
+            parent: Some(parent),
+            order: order
+        }
+    }
+
+    pub fn root(order: i32) -> Self {
+        Self {
+            parent: None,
+            order: order
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupUICom {
+    pub sprite: String,
+    pub vertical: bool,
+    pub padding: UIEdges,
+    pub spacing: u32
+}
+
+impl Component for GroupUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl GroupUICom {
+    pub fn new(sprite: &str, vertical: bool) -> Self {
+        Self {
+            sprite: sprite.to_string(),
+            vertical: vertical,
+            padding: UIEdges::default(),
+            spacing: 0
+        }
+    }
+
+    pub fn with_padding(mut self, padding: UIEdges) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing: u32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct ScrollUICom {
+    pub scrollbar: String,
+    pub scrollbar_handle: String,
+    pub scrollbar_width: u32,
+    pub offset: u32,
+    pub content_size: u32,
+    dragging: Option<i32>
+}
+
+impl Component for ScrollUICom {
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+impl ScrollUICom {
+    pub fn new(scrollbar: &str, scrollbar_handle: &str) -> Self {
+        Self {
+            scrollbar: scrollbar.to_string(),
+            scrollbar_handle: scrollbar_handle.to_string(),
+            scrollbar_width: 10,
+            offset: 0,
+            content_size: 0,
+            dragging: None
+        }
+    }
+}
+
+// Group markers from before the UI had an explicit hierarchy, kept for compatibility. They are converted into UIParentCom and GroupUICom on the next frame
 
 #[derive(Component, Debug)]
 #[storage(DenseVecStorage)]
@@ -323,59 +1788,340 @@ impl StartHorizontalGroupCom {
     }
 }
 
+#[derive(Component, Debug)]
+#[storage(DenseVecStorage)]
+pub struct StartScrollGroupCom {
+    pub sprite: String,
+    pub scrollbar: String,
+    pub scrollbar_handle: String,
+    pub vertical: bool
+}
+
+impl StartScrollGroupCom {
+    pub fn new(sprite: &str, scrollbar: &str, scrollbar_handle: &str, vertical: bool) -> Self {
+        Self {
+            sprite: sprite.to_string(),
+            scrollbar: scrollbar.to_string(),
+            scrollbar_handle: scrollbar_handle.to_string(),
+            vertical: vertical
+        }
+    }
+}
+
 #[derive(Component, Debug, DefaultConstructor)]
 #[storage(DenseVecStorage)]
 pub struct EndGroupCom;
 
+type LegacyGroupStorage<'a> = (WriteStorage<'a, StartVerticalGroupCom>,
+    WriteStorage<'a, StartHorizontalGroupCom>,
+    WriteStorage<'a, StartScrollGroupCom>,
+    WriteStorage<'a, EndGroupCom>);
+
+// Gives every element that is not part of the hierarchy yet a parent, following the order the group markers were created in
+fn convert_legacy_groups(entities: &Entities, constraints: &mut WriteStorage<ConstraintCom>, parents: &mut WriteStorage<UIParentCom>, groups: &mut WriteStorage<GroupUICom>, scrolls: &mut WriteStorage<ScrollUICom>, legacy: &mut LegacyGroupStorage) {
+    let (v_group_start, h_group_start, scroll_group_start, group_end) = legacy;
+    if (&*v_group_start, ).join().next().is_none() && (&*h_group_start, ).join().next().is_none() && (&*scroll_group_start, ).join().next().is_none() && (&*group_end, ).join().next().is_none() {
+        return;
+    }
+
+    let mut next_order: HashMap<Option<Entity>, i32> = HashMap::new();
+    for parent in parents.join() {
+        let order = next_order.entry(parent.parent).or_insert(0);
+        *order = (*order).max(parent.order + 1);
+    }
+
+    let mut stack: Vec<Option<Entity>> = vec![None];
+    let mut converted: Vec<(Entity, UIParentCom)> = Vec::new();
+    let mut ends: Vec<Entity> = Vec::new();
+    for (entity, _, ()) in (entities, &*constraints, !&*parents).join() {
+        if group_end.contains(entity) {
+            if stack.len() > 1 {
+                stack.pop();
+            }
+            ends.push(entity);
+            continue;
+        }
+
+        let parent = *stack.last().unwrap();
+        let order = next_order.entry(parent).or_insert(0);
+        converted.push((entity, UIParentCom { parent: parent, order: *order }));
+        *order += 1;
+
+        if let Some(group) = v_group_start.remove(entity) {
+            groups.insert(entity, GroupUICom::new(&group.sprite, true)).unwrap();
+            stack.push(Some(entity));
+        } else if let Some(group) = h_group_start.remove(entity) {
+            groups.insert(entity, GroupUICom::new(&group.sprite, false)).unwrap();
+            stack.push(Some(entity));
+        } else if let Some(group) = scroll_group_start.remove(entity) {
+            groups.insert(entity, GroupUICom::new(&group.sprite, group.vertical)).unwrap();
+            scrolls.insert(entity, ScrollUICom::new(&group.scrollbar, &group.scrollbar_handle)).unwrap();
+            stack.push(Some(entity));
+        }
+    }
+
+    for (entity, parent) in converted {
+        parents.insert(entity, parent).unwrap();
+    }
+    for entity in ends {
+        group_end.remove(entity);
+        constraints.remove(entity);
+    }
+}
+
+enum UINode {
+    Element(Entity),
+    End
+}
+
+// Deletes the elements whose parent was deleted, as well as everything inside them
+fn delete_orphans(entities: &Entities, parents: &WriteStorage<UIParentCom>) {
+    let mut deleted: HashSet<Entity> = HashSet::new();
+    loop {
+        let orphans: Vec<Entity> = (entities, parents).join()
+            .filter(|(entity, parent)| entities.is_alive(*entity) && !deleted.contains(entity) && parent.parent.map_or(false, |p| !entities.is_alive(p) || deleted.contains(&p)))
+            .map(|(entity, _)| entity)
+            .collect();
+        if orphans.is_empty() {
+            break;
+        }
+        for entity in orphans {
+            entities.delete(entity).ok();
+            deleted.insert(entity);
+        }
+    }
+}
+
+// Flattens the hierarchy into the order it is laid out in, the children of each group are followed by its end node
+fn ui_tree(entities: &Entities, constraints: &WriteStorage<ConstraintCom>, parents: &WriteStorage<UIParentCom>, groups: &WriteStorage<GroupUICom>) -> Vec<UINode> {
+    let mut children: HashMap<Option<Entity>, Vec<(i32, Entity)>> = HashMap::new();
+    for (entity, _, parent) in (entities, constraints, parents.maybe()).join() {
+        let (parent, order) = parent.map_or((None, 0), |p| (p.parent, p.order));
+        children.entry(parent).or_insert_with(Vec::new).push((order, entity));
+    }
+    for siblings in children.values_mut() {
+        siblings.sort();
+    }
+
+    fn visit(entity: Entity, children: &HashMap<Option<Entity>, Vec<(i32, Entity)>>, groups: &WriteStorage<GroupUICom>, nodes: &mut Vec<UINode>) {
+        nodes.push(UINode::Element(entity));
+        if groups.contains(entity) {
+            for (_, child) in children.get(&Some(entity)).into_iter().flatten() {
+                visit(*child, children, groups, nodes);
+            }
+            nodes.push(UINode::End);
+        }
+    }
+
+    let mut nodes = Vec::new();
+    for (_, root) in children.get(&None).into_iter().flatten() {
+        visit(*root, &children, groups, &mut nodes);
+    }
+    nodes
+}
+
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum PositionConstraint {
     Start,
     Center,
     End,
-    StartPixelOffset(u32)
+    StartPixelOffset(u32),
+    CenterPixelOffset(i32),
+    EndPixelOffset(u32),
+    StartProportionOffset(f32),
+    EndProportionOffset(f32)
 }
 
 impl PositionConstraint {
+    // Elements that do not fit into their container are placed at its start rather than before it
     fn as_pixels(&self, size: u32, container_size: u32) -> u32 {
+        let free = container_size.saturating_sub(size);
         match self {
             PositionConstraint::Start => 0,
-            PositionConstraint::Center => (container_size / 2) - (size / 2),
-            PositionConstraint::End => container_size - size,
-            PositionConstraint::StartPixelOffset(offset) => offset.clone()
+            PositionConstraint::Center => free / 2,
+            PositionConstraint::End => free,
+            PositionConstraint::StartPixelOffset(offset) => *offset,
+            PositionConstraint::CenterPixelOffset(offset) => (((free / 2) as i64) + *offset as i64).max(0).min(u32::MAX as i64) as u32,
+            PositionConstraint::EndPixelOffset(offset) => free.saturating_sub(*offset),
+            PositionConstraint::StartProportionOffset(proportion) => (proportion * container_size as f32) as u32,
+            PositionConstraint::EndProportionOffset(proportion) => free.saturating_sub((proportion * container_size as f32) as u32)
         }
     }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum SizeConstraint {
     Proportion(f32),
     Pixels(u32),
     NegativePixels(u32),
-    Fill
+    Fill,
+    FitContent
 }
 
 impl SizeConstraint {
-    fn as_pixels(&self, container_size: u32) -> u32 {
+    fn as_pixels(&self, container_size: u32, content_size: u32) -> u32 {
         match self {
             SizeConstraint::Proportion(proportion) => (proportion * container_size as f32).floor() as u32,
-            SizeConstraint::Pixels(pixels) => pixels.clone(),
-            SizeConstraint::NegativePixels(pixels) => container_size - pixels.clone(),
-            SizeConstraint::Fill => container_size
+            SizeConstraint::Pixels(pixels) => *pixels,
+            SizeConstraint::NegativePixels(pixels) => container_size.saturating_sub(*pixels),
+            SizeConstraint::Fill => container_size,
+            SizeConstraint::FitContent => content_size
         }
     }
 }
 
 pub fn register(world: &mut World) {
     world.insert(CameraRes::default());
+    world.insert(UIFocusRes::default());
+    world.insert(UITheme::default());
+    world.insert(UIScaleRes::default());
     world.register::<SpriteCom>();
     world.register::<TextCom>();
     world.register::<ConstraintCom>();
     world.register::<TextUICom>();
     world.register::<ButtonUICom>();
     world.register::<TextFieldUICom>();
+    world.register::<CheckboxUICom>();
+    world.register::<SliderUICom>();
+    world.register::<DropdownUICom>();
+    world.register::<ProgressBarUICom>();
+    world.register::<ImageUICom>();
+    world.register::<StyleUICom>();
+    world.register::<UIParentCom>();
+    world.register::<GroupUICom>();
+    world.register::<ScrollUICom>();
     world.register::<StartVerticalGroupCom>();
     world.register::<StartHorizontalGroupCom>();
+    world.register::<StartScrollGroupCom>();
     world.register::<EndGroupCom>();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{TextFieldUICom, UITheme};
+
+    fn field(text: &str) -> TextFieldUICom {
+        TextFieldUICom::new("background", text, "font", "field")
+    }
+
+    #[test]
+    fn insert_at_cursor() {
+        let mut f = field("held");
+        f.move_cursor(2, false);
+        assert!(f.insert("l"));
+        assert_eq!(f.text, "helld");
+        assert_eq!(f.cursor, 3);
+        assert_eq!(f.selection(), None);
+    }
+
+    #[test]
+    fn insert_replaces_selection() {
+        let mut f = field("hello world");
+        f.move_cursor(6, false);
+        f.move_cursor(11, true);
+        assert_eq!(f.selected_text(), "world");
+        assert!(f.insert("there"));
+        assert_eq!(f.text, "hello there");
+        assert_eq!(f.cursor, 11);
+    }
+
+    #[test]
+    fn insert_filters_control_characters_and_respects_max_length() {
+        let mut f = field("ab");
+        f.max_length = Some(4);
+        assert!(f.insert("c\nde"));
+        assert_eq!(f.text, "abcd");
+        assert!(!f.insert("e"));
+        assert_eq!(f.text, "abcd");
+    }
+
+    #[test]
+    fn insert_multibyte() {
+        let mut f = field("ñu");
+        f.move_cursor(1, false);
+        assert!(f.insert("é"));
+        assert_eq!(f.text, "ñéu");
+        assert_eq!(f.cursor, 2);
+    }
+
+    #[test]
+    fn backspace_and_delete() {
+        let mut f = field("abc");
+        f.move_cursor(1, false);
+        assert!(f.backspace());
+        assert_eq!(f.text, "bc");
+        assert!(!f.backspace());
+        assert!(f.delete());
+        assert_eq!(f.text, "c");
+        f.move_cursor(usize::MAX, false);
+        assert!(!f.delete());
+        assert_eq!(f.text, "c");
+    }
+
+    #[test]
+    fn backspace_deletes_selection() {
+        let mut f = field("abcdef");
+        f.move_cursor(4, false);
+        f.move_cursor(1, true);
+        assert!(f.backspace());
+        assert_eq!(f.text, "aef");
+        assert_eq!(f.cursor, 1);
+    }
+
+    #[test]
+    fn select_all_and_delete() {
+        let mut f = field("text");
+        f.select_all();
+        assert_eq!(f.selected_text(), "text");
+        assert!(f.delete());
+        assert_eq!(f.text, "");
+        assert_eq!(f.cursor, 0);
+    }
+
+    #[test]
+    fn move_collapses_selection() {
+        let mut f = field("abcdef");
+        f.move_cursor(2, false);
+        f.move_cursor(4, true);
+        f.move_left(false);
+        assert_eq!((f.cursor, f.selection()), (2, None));
+
+        f.move_cursor(4, true);
+        f.move_right(false);
+        assert_eq!((f.cursor, f.selection()), (4, None));
+
+        f.move_cursor(2, true);
+        f.move_right(false);
+        assert_eq!((f.cursor, f.selection()), (4, None));
+    }
+
+    #[test]
+    fn move_extends_selection() {
+        let mut f = field("abc");
+        f.move_cursor(1, false);
+        f.move_right(true);
+        f.move_right(true);
+        assert_eq!(f.selected_text(), "bc");
+        f.move_right(true);
+        assert_eq!(f.cursor, 3);
+        f.move_left(true);
+        assert_eq!(f.selected_text(), "b");
+    }
+
+    #[test]
+    fn masked_display() {
+        let mut f = field("pässword");
+        f.masked = true;
+        assert_eq!(f.display_text(), "********");
+    }
+
+    #[test]
+    fn malformed_theme_is_not_loaded() {
+        let path = std::env::temp_dir().join(format!("invader-theme-{}.mth", std::process::id()));
+        std::fs::write(&path, [0xff, 0xff, 0xff]).unwrap();
+        assert!(UITheme::from_file(path.to_str().unwrap()).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
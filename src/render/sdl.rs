@@ -1,11 +1,11 @@
 use std::collections::HashMap;
 
-use sdl2::Sdl;
+use sdl2::{Sdl, VideoSubsystem};
 use sdl2::pixels::Color;
-use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::render::{Canvas, Texture, TextureCreator, BlendMode};
 use sdl2::video::{Window, WindowContext};
 use sdl2::image::{Sdl2ImageContext, InitFlag, LoadTexture};
-use sdl2::rect::Rect;
+use sdl2::rect::{Rect, Point};
 use sdl2::ttf::{Sdl2TtfContext, Font};
 
 use nalgebra::Vector2;
@@ -15,11 +15,13 @@ use crate::misc::Vector;
 pub struct SDLRenderImpl<'a> {
     sprite_cache: SpriteCache<'a>,
     font_cache: FontCache<'a>,
-    context: SDLContext
+    context: SDLContext,
+    scale: f32,
+    font_fallbacks: HashMap<String, String>
 }
 
 impl SDLRenderImpl<'_> {
-    pub fn render(&mut self, sprite_name: &str, sprite_pos: Vector, sprite_dim: Vector, cam_pos: Vector, cam_zoom: f32, cam_screen: Vector2<u32>) {
+    pub fn render(&mut self, sprite_name: &str, sprite_pos: Vector, sprite_dim: Vector, sprite_rotation: f32, cam_pos: Vector, cam_zoom: f32, cam_screen: Vector2<u32>) {
         // Camera transformation
         let pos_x = (((sprite_pos.x - cam_pos.x) / 5.0 * cam_zoom + 1.0) / 2.0 * cam_screen.x as f32) as i32;
         let pos_y = ((-(sprite_pos.y - cam_pos.y + sprite_dim.y) / 5.0 * cam_zoom * (cam_screen.x as f32 / cam_screen.y as f32) + 1.0) / 2.0 * cam_screen.y as f32) as i32;
@@ -27,13 +29,14 @@ impl SDLRenderImpl<'_> {
         let dim_y = (sprite_dim.y * cam_screen.y as f32 * cam_zoom / 5.0 / 2.0 * (cam_screen.x as f32 / cam_screen.y as f32)) as u32;
 
         match self.sprite_cache.get(sprite_name) {
-            Some(sprite) => self.context.canvas.copy(sprite, None, Rect::new(pos_x, pos_y, dim_x, dim_y)).unwrap(),
+            // Sprites rotate around their position, which is their bottom left corner, and SDL rotates clockwise in degrees
+            Some(sprite) => self.context.canvas.copy_ex(sprite, None, Rect::new(pos_x, pos_y, dim_x, dim_y), -sprite_rotation.to_degrees() as f64, Point::new(0, dim_y as i32), false, false).unwrap(),
             None => eprintln!("sprite \"{}\" not found in cache", sprite_name)
         }
     }
 
-    pub fn write(&mut self, text: &str, font: &str, text_pos: Vector, text_dim: Vector, cam_pos: Vector, cam_zoom: f32, cam_screen: Vector2<u32>) {
-        match self.font_cache.get(font) {
+    pub fn write(&mut self, text: &str, font: &str, text_pos: Vector, text_dim: Vector, text_rotation: f32, cam_pos: Vector, cam_zoom: f32, cam_screen: Vector2<u32>) {
+        match self.font(text, font) {
             Some((font, color)) => {
                 let text_surface = font.render(text).blended(color.clone()).unwrap();
                 let (w, h) = text_surface.size();
@@ -46,7 +49,7 @@ impl SDLRenderImpl<'_> {
                 let dim_x = (text_dim.x * cam_screen.x as f32 * cam_zoom / 5.0 / 2.0) as u32;
                 let dim_y = (text_dim.y * cam_screen.y as f32 * cam_zoom / 5.0 / 2.0 * (cam_screen.x as f32 / cam_screen.y as f32)) as u32;
 
-                self.context.canvas.copy(&text_texture, None, Rect::new(pos_x, pos_y, dim_x, dim_y)).unwrap();
+                self.context.canvas.copy_ex(&text_texture, None, Rect::new(pos_x, pos_y, dim_x, dim_y), -text_rotation.to_degrees() as f64, Point::new(0, dim_y as i32), false, false).unwrap();
             },
             None => eprintln!("font \"{}\" not found in cache", font)
         }
@@ -63,9 +66,15 @@ impl SDLRenderImpl<'_> {
     }
 
     pub fn write_ss(&mut self, text: &str, font: &str, text_pos: Vector2<i32>, text_dim: Vector2<u32>) -> bool {
-        match self.font_cache.get(font) {
+        self.write_colored_ss(text, font, None, text_pos, text_dim)
+    }
+
+    // Writes the text in the given color instead of the one the font was loaded with
+    pub fn write_colored_ss(&mut self, text: &str, font: &str, text_color: Option<(u8, u8, u8)>, text_pos: Vector2<i32>, text_dim: Vector2<u32>) -> bool {
+        match self.font(text, font) {
             Some((font, color)) => {
-                match font.render(text).blended(color.clone()) {
+                let color = text_color.map_or(*color, |(r, g, b)| Color::RGB(r, g, b));
+                match font.render(text).blended(color) {
                     Ok(text_surface) => {
                         let (w, h) = text_surface.size();
                         let text_texture = self.context.texture_creator.create_texture_from_surface(text_surface).unwrap();
@@ -84,6 +93,102 @@ impl SDLRenderImpl<'_> {
         }
     }
 
+    pub fn fill_ss(&mut self, pos: Vector2<i32>, dim: Vector2<u32>, red: u8, green: u8, blue: u8, alpha: u8) {
+        self.context.canvas.set_draw_color(Color::RGBA(red, green, blue, alpha));
+        self.context.canvas.fill_rect(Rect::new(pos.x, pos.y, dim.x, dim.y)).unwrap();
+    }
+
+    pub fn set_clip_ss(&mut self, clip: Option<(Vector2<i32>, Vector2<u32>)>) {
+        self.context.canvas.set_clip_rect(clip.map(|(pos, dim)| Rect::new(pos.x, pos.y, dim.x, dim.y)));
+    }
+
+    // Width of the text when written with write_ss at the given height
+    pub fn text_width(&self, text: &str, font: &str, height: u32) -> u32 {
+        match self.font(text, font) {
+            Some((font, _)) => match font.size_of(text) {
+                Ok((w, h)) if h > 0 => (height as f32 * (w as f32 / h as f32)) as u32,
+                _ => 0
+            },
+            None => 0
+        }
+    }
+
+    // Size of the text when rendered at the natural height of the font
+    pub fn text_size(&self, text: &str, font: &str) -> Vector2<u32> {
+        match self.font(text, font).map(|(font, _)| font.size_of(text)) {
+            Some(Ok((w, h))) => Vector2::new(w, h),
+            _ => Vector2::new(0, 0)
+        }
+    }
+
+    pub fn sprite_size(&self, sprite: &str) -> Vector2<u32> {
+        match self.sprite_cache.get(sprite).map(|texture| texture.query()) {
+            Some(query) => Vector2::new(query.width, query.height),
+            None => Vector2::new(0, 0)
+        }
+    }
+
+    // Fonts to use instead of the configured ones for text with glyphs they do not provide
+    pub fn set_font_fallbacks(&mut self, fallbacks: HashMap<String, String>) {
+        self.font_fallbacks = fallbacks;
+    }
+
+    fn font(&self, text: &str, name: &str) -> Option<&(Font<'_, 'static>, Color)> {
+        let font = self.font_cache.get(name);
+        match (font, self.font_fallbacks.get(name)) {
+            (Some((primary, _)), Some(fallback)) if text.chars().any(|c| primary.find_glyph(c).is_none()) => self.font_cache.get(fallback).or(font),
+            (None, Some(fallback)) => self.font_cache.get(fallback),
+            _ => font
+        }
+    }
+
+    pub fn font_color(&self, font: &str) -> Option<(u8, u8, u8)> {
+        self.font_cache.get(font).map(|(_, color)| (color.r, color.g, color.b))
+    }
+
+    pub fn clipboard(&self) -> String {
+        self.context.video.clipboard().clipboard_text().unwrap_or_default()
+    }
+
+    pub fn set_clipboard(&self, text: &str) {
+        if let Err(e) = self.context.video.clipboard().set_clipboard_text(text) {
+            eprintln!("could not write to clipboard: {}", e);
+        }
+    }
+
+    // Text input events are only generated while this is active, the rect positions the IME candidate window
+    pub fn set_text_input(&mut self, area: Option<(Vector2<i32>, Vector2<u32>)>) {
+        let text_input = self.context.video.text_input();
+        match area {
+            Some((pos, dim)) => {
+                // The rect is in window coordinates, which are not affected by the scale of the renderer
+                let pos = Vector2::new((pos.x as f32 * self.scale) as i32, (pos.y as f32 * self.scale) as i32);
+                let dim = Vector2::new((dim.x as f32 * self.scale) as u32, (dim.y as f32 * self.scale) as u32);
+                text_input.set_rect(Rect::new(pos.x, pos.y, dim.x, dim.y));
+                if !text_input.is_active() {
+                    text_input.start();
+                }
+            },
+            None => if text_input.is_active() {
+                text_input.stop();
+            }
+        }
+    }
+
+    // Everything drawn with the _ss functions afterwards is scaled by this factor
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+        self.context.canvas.set_scale(scale, scale).unwrap();
+    }
+
+    // Diagonal DPI of the display the window is on
+    pub fn display_dpi(&self) -> Option<f32> {
+        self.context.canvas.window().display_index()
+            .and_then(|display| self.context.video.display_dpi(display))
+            .map(|(dpi, _, _)| dpi)
+            .ok()
+    }
+
     pub fn pre(&mut self) {
         self.context.canvas.set_draw_color(Color::RGB(50, 50, 60));
         self.context.canvas.clear();
@@ -128,12 +233,15 @@ impl SDLRenderImpl<'_> {
             .build().unwrap();
 
         canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.set_blend_mode(BlendMode::Blend);
         canvas.clear();
         canvas.present();
+        video.text_input().stop();
 
         let texture_creator = canvas.texture_creator();
 
         let context = SDLContext {
+            video: video,
             canvas: canvas,
             _image: sdl_image_context,
             font: sdl_font_context,
@@ -144,11 +252,14 @@ impl SDLRenderImpl<'_> {
             sprite_cache: SpriteCache::new(),
             font_cache: FontCache::new(),
             context: context,
+            scale: 1.0,
+            font_fallbacks: HashMap::new()
         }
     }
 }
 
 struct SDLContext {
+    video: VideoSubsystem,
     canvas: Canvas<Window>,
     _image: Sdl2ImageContext,
     font: Sdl2TtfContext,